@@ -0,0 +1,248 @@
+use crate::disasm::INSTRUCTIONS;
+use crate::error::{Error, Result};
+
+use std::collections::HashMap;
+
+// Assembles source in the disassembler's syntax, e.g. "DRW V0, V1, 5", one instruction per line,
+// into a program loaded at load_addr. Labels end with ':', e.g. "loop: JP loop", and can be used
+// in place of addresses, before or after they're defined. DB and DW write bytes and words of data,
+// e.g. "DB 0xF0, 0x90, 0xF0". Numbers are decimal, hex with 0x, or binary with 0b, and comments
+// start with ';'.
+
+struct Statement<'a> {
+    label: Option<&'a str>,
+    mnemonic: &'a str,
+    operands: Vec<&'a str>,
+}
+
+pub fn assemble(source: &str, load_addr: u16) -> Result<Vec<u8>> {
+    let statements = source
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| parse_line(line).map_err(|message| asm_error(idx, message)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // labels are found first, so jumps can go forward
+    let mut labels = HashMap::new();
+    let mut addr = load_addr as usize;
+    for (idx, statement) in statements.iter().enumerate() {
+        if let Some(label) = statement.label {
+            if labels.insert(label, addr).is_some() {
+                return Err(asm_error(
+                    idx,
+                    format!("Label \"{}\" is already defined", label),
+                ));
+            }
+        }
+        addr += match statement.mnemonic.to_uppercase().as_str() {
+            "" => 0,
+            "DB" => statement.operands.len(),
+            "DW" => 2 * statement.operands.len(),
+            _ => 2,
+        };
+    }
+
+    let mut bytes = vec![];
+    for (idx, statement) in statements.iter().enumerate() {
+        encode(statement, &labels, &mut bytes).map_err(|message| asm_error(idx, message))?;
+    }
+
+    Ok(bytes)
+}
+
+fn parse_line(line: &str) -> std::result::Result<Statement<'_>, String> {
+    let line = line.split(';').next().unwrap_or_default().trim();
+    let (label, line) = match line.split_once(':') {
+        Some((label, rest)) => {
+            let label = label.trim();
+            if label.is_empty() || !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("Invalid label \"{}\"", label));
+            }
+            (Some(label), rest.trim())
+        }
+        None => (None, line),
+    };
+    let (mnemonic, operands) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let operands = operands
+        .split(',')
+        .map(str::trim)
+        .filter(|operand| !operand.is_empty())
+        .collect();
+
+    Ok(Statement {
+        label,
+        mnemonic,
+        operands,
+    })
+}
+
+fn encode(
+    statement: &Statement,
+    labels: &HashMap<&str, usize>,
+    bytes: &mut Vec<u8>,
+) -> std::result::Result<(), String> {
+    let mnemonic = statement.mnemonic.to_uppercase();
+    let operands = &statement.operands;
+    match mnemonic.as_str() {
+        "" => (),
+        "DB" | "DW" => {
+            let max = if mnemonic == "DB" { 0xFF } else { 0xFFFF };
+            if operands.is_empty() {
+                return Err(format!("{} needs at least one value", mnemonic));
+            }
+            for operand in operands {
+                let val = value(operand, labels)
+                    .filter(|val| *val <= max)
+                    .ok_or_else(|| format!("Invalid value \"{}\" for {}", operand, mnemonic))?;
+                if mnemonic == "DW" {
+                    bytes.push((val >> 8) as u8);
+                }
+                bytes.push(val as u8);
+            }
+        }
+        _ => {
+            let opcode = encode_instruction(&mnemonic, operands, labels)?;
+            bytes.extend(opcode.to_be_bytes());
+        }
+    }
+
+    Ok(())
+}
+
+// finds the instruction with the mnemonic that the operands fit, e.g. "LD V0, DT" rather than
+// "LD Vx, kk", as DT isn't a number
+fn encode_instruction(
+    mnemonic: &str,
+    operands: &[&str],
+    labels: &HashMap<&str, usize>,
+) -> std::result::Result<u16, String> {
+    let mut known = false;
+    for (syntax, _, pattern) in INSTRUCTIONS {
+        let (syntax_mnemonic, syntax_operands) = syntax.split_once(' ').unwrap_or((syntax, ""));
+        if syntax_mnemonic != mnemonic {
+            continue;
+        }
+        known = true;
+
+        let syntax_operands = syntax_operands
+            .split(", ")
+            .filter(|operand| !operand.is_empty())
+            .collect::<Vec<_>>();
+        if syntax_operands.len() != operands.len() {
+            continue;
+        }
+        let bits = syntax_operands
+            .iter()
+            .zip(operands)
+            .map(|(kind, operand)| operand_bits(kind, operand, labels))
+            .collect::<Option<Vec<_>>>();
+        if let Some(bits) = bits {
+            return Ok(bits
+                .into_iter()
+                .fold(*pattern, |opcode, bits| opcode | bits));
+        }
+    }
+
+    if known {
+        Err(format!(
+            "Invalid operands for {}: {}",
+            mnemonic,
+            operands.join(", ")
+        ))
+    } else {
+        Err(format!("Unknown instruction \"{}\"", mnemonic))
+    }
+}
+
+// the bits an operand sets in the opcode, if it fits the kind in the instruction's syntax
+fn operand_bits(kind: &str, operand: &str, labels: &HashMap<&str, usize>) -> Option<u16> {
+    match kind {
+        "Vx" => register(operand).map(|reg| reg << 8),
+        "Vy" => register(operand).map(|reg| reg << 4),
+        "nnn" => value(operand, labels).filter(|val| *val <= 0xFFF),
+        "kk" => number(operand).filter(|val| *val <= 0xFF),
+        "n" => number(operand).filter(|val| *val <= 0xF),
+        _ if kind.eq_ignore_ascii_case(operand) => Some(0),
+        _ => None,
+    }
+}
+
+fn register(operand: &str) -> Option<u16> {
+    let digit = operand.strip_prefix(|c| c == 'V' || c == 'v')?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u16::from_str_radix(digit, 16).ok()
+}
+
+// a number, or the address of a label
+fn value(operand: &str, labels: &HashMap<&str, usize>) -> Option<u16> {
+    number(operand).or_else(|| {
+        labels
+            .get(operand)
+            .and_then(|addr| u16::try_from(*addr).ok())
+    })
+}
+
+fn number(operand: &str) -> Option<u16> {
+    let operand = operand.to_lowercase();
+    if let Some(hex) = operand.strip_prefix("0x") {
+        u16::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = operand.strip_prefix("0b") {
+        u16::from_str_radix(binary, 2).ok()
+    } else {
+        operand.parse().ok()
+    }
+}
+
+// line numbers are counted from 1, as editors show them
+fn asm_error(line_idx: usize, message: String) -> Error {
+    Error::Asm {
+        line: line_idx + 1,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn assembles_labels_and_data() {
+        let source = "\
+            start: LD I, sprite ; the sprite is after the code
+                   drw	v0, v1, 5
+            loop:  JP loop
+            sprite:
+                   DB 0xF0, 0b10010000, 144
+                   DW 0x1234";
+        assert_eq!(
+            assemble(source, 0x200).unwrap(),
+            vec![0xA2, 0x06, 0xD0, 0x15, 0x12, 0x04, 0xF0, 0x90, 0x90, 0x12, 0x34]
+        );
+    }
+
+    #[test]
+    fn reassembles_disassembly() {
+        for opcode in [
+            0x00E0, 0x1ABC, 0x3A12, 0x5120, 0x8AB4, 0xB300, 0xD01F, 0xF50A, 0xF265,
+        ] {
+            assert_eq!(
+                assemble(&disassemble(opcode), 0x200).unwrap(),
+                opcode.to_be_bytes()
+            );
+        }
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = assemble("CLS\nLD V0, 0x100", 0x200).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Assembly error on line 2: Invalid operands for LD: V0, 0x100"
+        );
+        let err = assemble("\n\nJP nowhere", 0x200).unwrap_err();
+        assert!(err.to_string().starts_with("Assembly error on line 3"));
+    }
+}
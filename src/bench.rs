@@ -0,0 +1,35 @@
+use crate::cpu::Cpu;
+use crate::display::Display;
+use crate::error::{Error, Result};
+use crate::keyboard::Keyboard;
+use crate::options::Options;
+
+use std::{path::PathBuf, time::Instant};
+
+// Runs a ROM headless, without the 60 FPS throttle, and prints the results as "key=value" lines
+pub fn run(rom_path: PathBuf, options: &Options) -> Result<()> {
+    let options = &options.for_variant().map_err(Error::Options)?;
+    let keyboard = Keyboard::new(options.key_ttl, options.arrow_keys, options.pause_key);
+    let mut cpu = Cpu::new(Display::new(options), keyboard, options);
+
+    let max_instructions = options.cycles.unwrap_or(options.bench_instructions);
+    let start = Instant::now();
+    let executed = cpu.run_headless(rom_path, max_instructions)?;
+    let elapsed = start.elapsed();
+
+    println!("instructions={}", executed);
+    println!("frames={}", cpu.frame());
+    println!("elapsed_ms={:.3}", elapsed.as_secs_f64() * 1000.0);
+    println!("ips={:.0}", executed as f64 / elapsed.as_secs_f64());
+    if cpu.should_quit() {
+        println!("stopped=self_jump");
+    } else if executed < max_instructions {
+        println!("stopped=waiting_for_key");
+    }
+    cpu.print_opcode_stats();
+    if let Some(path) = &options.dump_state_path {
+        cpu.state().save(path)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use crossterm::style::Color;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Records the display to an animated GIF, e.g. to share gameplay or a bug
+
+const SCALE: usize = 4; // image pixels per block
+const FRAME_STEP: u64 = 3; // emulated frames per GIF frame, i.e. 20 FPS
+const FRAME_DELAY: u16 = 5; // in 1/100 s, as GIF delays can't match 60 FPS exactly
+const BACKGROUND: [u8; 3] = [0x00, 0x00, 0x00]; // terminals' usual, as the real one can't be read
+
+pub struct GifCapture {
+    encoder: gif::Encoder<io::BufWriter<fs::File>>, // finishes the file when dropped
+    path: PathBuf,
+    frames: u64,
+    cols: usize,
+    rows: usize,
+}
+
+impl GifCapture {
+    // starts a new GIF in the dir, named by the time, e.g. chip-8-1700000000.gif, in the display's
+    // colors as they are when it starts
+    pub fn create(
+        dir: &Path,
+        cols: usize,
+        rows: usize,
+        color: Color,
+        inverted: bool,
+    ) -> Result<Self, io::Error> {
+        fs::create_dir_all(dir)?;
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        let path = dir.join(format!("chip-8-{}.gif", secs));
+
+        // unlit, then lit
+        let palette = match inverted {
+            false => [BACKGROUND, rgb(color)].concat(),
+            true => [rgb(color), BACKGROUND].concat(),
+        };
+        let file = io::BufWriter::new(fs::File::create(&path)?);
+        let mut encoder =
+            gif::Encoder::new(file, (cols * SCALE) as u16, (rows * SCALE) as u16, &palette)
+                .map_err(gif_error)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(gif_error)?;
+
+        Ok(Self {
+            encoder,
+            path,
+            frames: 0,
+            cols,
+            rows,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // called every frame, with 1 for each lit block, row by row; only every few are kept
+    pub fn capture(&mut self, blocks: &[u8]) -> Result<(), io::Error> {
+        self.frames += 1;
+        if (self.frames - 1) % FRAME_STEP != 0 {
+            return Ok(());
+        }
+
+        let mut pixels = Vec::with_capacity(self.cols * self.rows * SCALE * SCALE);
+        for row in blocks.chunks(self.cols) {
+            let line = row
+                .iter()
+                .flat_map(|block| [*block; SCALE])
+                .collect::<Vec<_>>();
+            for _ in 0..SCALE {
+                pixels.extend_from_slice(&line);
+            }
+        }
+
+        let mut frame = gif::Frame::from_indexed_pixels(
+            (self.cols * SCALE) as u16,
+            (self.rows * SCALE) as u16,
+            &pixels,
+            None,
+        );
+        frame.delay = FRAME_DELAY;
+        self.encoder.write_frame(&frame).map_err(gif_error)
+    }
+}
+
+fn gif_error(err: gif::EncodingError) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("Failed to write GIF: {}", err),
+    )
+}
+
+// the usual xterm values, for named and 256-color palette colors
+fn rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Rgb { r, g, b } => [r, g, b],
+        Color::AnsiValue(val) => ansi_rgb(val),
+        Color::Black => ansi_rgb(0),
+        Color::DarkRed => ansi_rgb(1),
+        Color::DarkGreen => ansi_rgb(2),
+        Color::DarkYellow => ansi_rgb(3),
+        Color::DarkBlue => ansi_rgb(4),
+        Color::DarkMagenta => ansi_rgb(5),
+        Color::DarkCyan => ansi_rgb(6),
+        Color::Grey => ansi_rgb(7),
+        Color::DarkGrey => ansi_rgb(8),
+        Color::Red => ansi_rgb(9),
+        Color::Green => ansi_rgb(10),
+        Color::Yellow => ansi_rgb(11),
+        Color::Blue => ansi_rgb(12),
+        Color::Magenta => ansi_rgb(13),
+        Color::Cyan => ansi_rgb(14),
+        Color::White | Color::Reset => ansi_rgb(15),
+    }
+}
+
+fn ansi_rgb(val: u8) -> [u8; 3] {
+    const BASIC: [[u8; 3]; 16] = [
+        [0x00, 0x00, 0x00],
+        [0xCD, 0x00, 0x00],
+        [0x00, 0xCD, 0x00],
+        [0xCD, 0xCD, 0x00],
+        [0x00, 0x00, 0xEE],
+        [0xCD, 0x00, 0xCD],
+        [0x00, 0xCD, 0xCD],
+        [0xE5, 0xE5, 0xE5],
+        [0x7F, 0x7F, 0x7F],
+        [0xFF, 0x00, 0x00],
+        [0x00, 0xFF, 0x00],
+        [0xFF, 0xFF, 0x00],
+        [0x5C, 0x5C, 0xFF],
+        [0xFF, 0x00, 0xFF],
+        [0x00, 0xFF, 0xFF],
+        [0xFF, 0xFF, 0xFF],
+    ];
+    match val {
+        0..=15 => BASIC[val as usize],
+        // a 6x6x6 cube
+        16..=231 => {
+            let level = |idx: u8| if idx == 0 { 0 } else { 55 + idx * 40 };
+            let idx = val - 16;
+            [level(idx / 36), level(idx / 6 % 6), level(idx % 6)]
+        }
+        // then a gray ramp
+        _ => [8 + (val - 232) * 10; 3],
+    }
+}
@@ -0,0 +1,40 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+// Log of DRW collisions, i.e. draws that erased pixels and so set VF, one per line:
+// "<frame> <address> <I> <x>,<y> <erased>", e.g. "120 02A4 0300 12,5 3" for a sprite drawn at
+// (12, 5), as wrapped onto the screen, that erased 3 pixels
+
+pub struct CollisionLog {
+    writer: io::BufWriter<fs::File>,
+}
+
+impl CollisionLog {
+    pub fn create(path: &Path) -> Result<Self, io::Error> {
+        Ok(Self {
+            writer: io::BufWriter::new(fs::File::create(path)?),
+        })
+    }
+
+    pub fn log(
+        &mut self,
+        frame: u64,
+        addr: u16,
+        i: u16,
+        (x, y): (u16, u16),
+        erased: u16,
+    ) -> Result<(), io::Error> {
+        writeln!(
+            self.writer,
+            "{} {:04X} {:04X} {},{} {}",
+            frame, addr, i, x, y, erased
+        )
+    }
+
+    pub fn flush(&mut self) -> Result<(), io::Error> {
+        self.writer.flush()
+    }
+}
@@ -1,14 +1,43 @@
-use crate::display::{Display, COLS, ROWS};
+use crate::capture::GifCapture;
+use crate::collisions::CollisionLog;
+use crate::debugger::{self, DebugCommand};
+use crate::disasm;
+use crate::display::Display;
+use crate::error::{Error, Result};
+use crate::idle::IdleDetector;
 use crate::keyboard::Keyboard;
+use crate::options::{
+    EmptyStackBehavior, InitPattern, MemoryOverflowBehavior, Options, Quirks, UnknownOpcodeBehavior,
+};
+use crate::rom;
+use crate::settings;
+use crate::state::CpuState;
+use crate::stats::{DrawRate, OpcodeStats};
+use crate::timing::{FrameTiming, TimingLog};
+use crate::trace::{Registers, Trace};
+use crate::undo::{Undo, UndoLog};
 
-use rand::Rng;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use std::{
-    cmp, fs, io,
+    cmp,
+    collections::BTreeSet,
+    fs, io,
     time::{Duration, Instant},
 };
 
-const MEMORY_SIZE: usize = 4096;
-const FPS_INTERVAL: Duration = Duration::from_millis(1000 / 60);
+// levels of subroutine calls
+const STACK_DEPTH: usize = 16;
+// emulated frames per second, at which the timers count down
+const FPS: u32 = 60;
+const FPS_INTERVAL: Duration = Duration::from_millis(1000 / FPS as u64);
+// the delay timer's period, by the wall clock, exactly 60 Hz
+const DELAY_TIMER_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// longer gaps between wall clock checks of the delay timer are pauses, e.g. by the debugger
+const DELAY_TIMER_MAX_GAP: Duration = Duration::from_millis(100);
+// how many times faster turbo runs
+const TURBO_SPEEDUP: u8 = 4;
+// machine cycles (of 8 clocks, at 1.76 MHz) per frame, less those taken by the display's DMA
+const VIP_CYCLES_PER_FRAME: u32 = 3668 - 1024;
 
 const SPRITE_BYTES: [u8; 0x50] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -29,85 +58,805 @@ const SPRITE_BYTES: [u8; 0x50] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
+// SUPER-CHIP 8x10 font, loaded after the small font
+const BIG_SPRITES_ADDR: usize = SPRITE_BYTES.len();
+const BIG_SPRITE_BYTES: [u8; 0xA0] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x3C, 0x7E, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFE, 0xC3, 0xC3, 0xFE, 0xFE, 0xC3, 0xC3, 0xFE, 0xFC, // B
+    0x3C, 0x7E, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0x7E, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+// how far to run while halted by the debugger
+enum DebugStep {
+    Frame,
+    Instruction,
+}
+
 struct NextKeyParams {
     destination_idx: usize,
     valid_after: Instant,
+    pressed_key: Option<u8>, // pressed key, waiting to be released
 }
 
 pub struct Cpu {
-    memory: [u8; MEMORY_SIZE],
-    v: [u8; 0x10], // registers V0-VF
-    i: u16,        // "I" register
+    memory: Vec<u8>,  // sized by the memory option, always a power of 2
+    program: Vec<u8>, // as loaded, for cold resets
+    v: [u8; 0x10],    // registers V0-VF
+    i: u16,           // "I" register
     delay_timer: u8,
-    sound_timer: u8, // audio not implemented
-    pc: u16,         // program counter
+    wall_clock_dt: bool, // counts the delay timer down by the wall clock, mid-frame
+    delay_timer_checked: Instant, // when the wall clock was last checked
+    delay_timer_elapsed: Duration, // since the delay timer last counted down
+    sound_timer: u8,     // audio not implemented, but shown in the bottom bar
+    on_sound: Option<Box<dyn FnMut(bool)>>, // called when the sound starts (true) or stops
+    sound_playing: bool, // while the sound timer runs, except in turbo
+    pc: u16,             // program counter
+    start_state: Option<CpuState>, // loaded over the machine once the ROM is, to resume from
     stack: Vec<u16>,
+    flags: [u8; 8],                         // SUPER-CHIP "RPL user flags"
+    flags_path: Option<std::path::PathBuf>, // where flags persist between runs, if enabled
+    persist_flags: bool,
     last_tick: Instant,
     paused: bool,
+    debug_halted: bool,
+    debug_step: Option<DebugStep>,
+    debug_message: Option<String>, // result of the last debug prompt command
+    slow_draw: Option<Duration>,   // delay after each sprite pixel, rendered one at a time
     should_quit: bool,
-    speed: u16,
+    halt_on_self_jump: bool,       // test ROMs end by jumping to themselves
+    instructions: u64,             // number executed
+    max_instructions: Option<u64>, // stops emulation once reached
+    max_frames: Option<u64>,       // likewise
+    limit_reached: bool,
+    frame: u64, // number of completed cycles, at 60 per second, excluding those halted in debug
+    instructions_per_second: u32, // the speed setting
+    instructions_per_frame: u32, // the whole instructions of a second's worth, spread over FPS
+    instruction_carry: u32, // the fractions of an instruction left over, in FPSths, run once whole
+    frame_skip: u8, // frames not rendered after each rendered one, for slow terminals
+    frames_to_skip: u8, // before the next render
+    vip_timing: bool, // charges each opcode its COSMAC VIP cycle cost, rather than 1 per instruction
+    cycle_overrun: u32, // VIP cycles the last frame's final instruction ran past its budget
+    idle_detector: Option<IdleDetector>, // ends frames early when the ROM busy-waits
+    input_advance: bool, // runs only while a key is pressed, to think between moves in puzzles
+    load_addr: u16,
+    init_pattern: InitPattern,
+    quirks: Quirks,
+    font_path: Option<std::path::PathBuf>,
     next_key_params: Option<NextKeyParams>,
+    on_unknown: UnknownOpcodeBehavior,
+    on_empty_ret: EmptyStackBehavior,
+    on_bcd_overflow: MemoryOverflowBehavior,
+    rng: StdRng,
+    skipped_opcodes: BTreeSet<(u16, u16)>, // (address, opcode)
+    opcode_stats: Option<OpcodeStats>,
+    draw_rate: Option<DrawRate>,
+    trace: Option<Trace>,
+    timing_log: Option<TimingLog>,
+    collision_log: Option<CollisionLog>,
+    undo_log: Option<UndoLog>, // kept while debugging, to step back
+    gif_capture: Option<GifCapture>,
+    gif_paths: Vec<std::path::PathBuf>, // GIFs recorded, to list after exit
+    trace_notes: Vec<String>,           // details of the current instruction, for the trace log
     display: Display,
     keyboard: Keyboard,
 }
 
 impl Cpu {
-    pub fn new(display: Display, keyboard: Keyboard) -> Self {
-        Self {
-            memory: [0; MEMORY_SIZE],
+    pub fn new(display: Display, keyboard: Keyboard, options: &Options) -> Self {
+        let mut cpu = Self {
+            memory: vec![0; options.memory_size],
+            program: vec![],
             v: [0; 0x10],
             i: 0,
             delay_timer: 0,
+            wall_clock_dt: options.wall_clock_dt,
+            delay_timer_checked: Instant::now(),
+            delay_timer_elapsed: Duration::ZERO,
             sound_timer: 0,
-            pc: 0x200,
+            on_sound: None,
+            sound_playing: false,
+            pc: options.load_addr,
+            start_state: None,
             stack: vec![],
+            flags: [0; 8],
+            flags_path: None,
+            persist_flags: options.persist_flags,
             last_tick: Instant::now(),
             paused: false,
+            debug_halted: false,
+            debug_step: None,
+            debug_message: None,
+            slow_draw: options.slow_draw,
             next_key_params: None,
             should_quit: false,
-            speed: (700.0 * FPS_INTERVAL.as_secs_f32()) as u16, // CPU cycles per frame
+            halt_on_self_jump: options.halt_on_self_jump,
+            instructions: 0,
+            max_instructions: options.cycles,
+            max_frames: options.frames,
+            limit_reached: false,
+            frame: 0,
+            instructions_per_second: 0,
+            instructions_per_frame: 0,
+            instruction_carry: 0,
+            frame_skip: options.frame_skip,
+            frames_to_skip: 0,
+            vip_timing: options.vip_timing,
+            cycle_overrun: 0,
+            idle_detector: if options.idle_skip {
+                Some(IdleDetector::new())
+            } else {
+                None
+            },
+            input_advance: options.input_advance,
+            load_addr: options.load_addr,
+            init_pattern: options.init_pattern,
+            quirks: options.quirks,
+            font_path: options.font_path.clone(),
+            on_unknown: options.on_unknown,
+            on_empty_ret: options.on_empty_ret,
+            on_bcd_overflow: options.on_bcd_overflow,
+            rng: match options.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => StdRng::from_entropy(),
+            },
+            skipped_opcodes: BTreeSet::new(),
+            opcode_stats: if options.opcode_stats {
+                Some(OpcodeStats::new())
+            } else {
+                None
+            },
+            draw_rate: if options.draw_rate {
+                Some(DrawRate::new())
+            } else {
+                None
+            },
+            trace: None,
+            timing_log: None,
+            collision_log: None,
+            undo_log: if options.debug {
+                Some(UndoLog::new())
+            } else {
+                None
+            },
+            gif_capture: None,
+            gif_paths: vec![],
+            trace_notes: vec![],
             keyboard,
             display,
+        };
+        cpu.set_instructions_per_second(options.speed);
+        let key_labels = cpu.keyboard.key_labels();
+        cpu.display.set_key_labels(key_labels);
+        cpu.fill_memory();
+        cpu.fill_registers();
+
+        cpu
+    }
+
+    // recomputes the instructions run each frame, so the speed holds whatever the frame rate
+    fn set_instructions_per_second(&mut self, ips: u32) {
+        self.instructions_per_second = ips;
+        self.instructions_per_frame = ips / FPS;
+        self.instruction_carry = 0;
+    }
+
+    fn fill_memory(&mut self) {
+        for idx in 0..self.memory.len() {
+            self.memory[idx] = self.fill_byte();
+        }
+    }
+
+    fn fill_registers(&mut self) {
+        for idx in 0..self.v.len() {
+            self.v[idx] = self.fill_byte();
         }
     }
 
-    pub fn init(&mut self, path: std::path::PathBuf) -> Result<(), io::Error> {
-        self.read_sprites_into_memory();
+    fn fill_byte(&mut self) -> u8 {
+        match self.init_pattern {
+            InitPattern::Zero => 0x00,
+            InitPattern::Ff => 0xFF,
+            InitPattern::Random => self.rng.gen(),
+        }
+    }
+
+    pub fn init(&mut self, path: std::path::PathBuf) -> Result<()> {
+        if self.persist_flags {
+            self.load_flags(&path)?;
+        }
+        self.read_sprites_into_memory()?;
         self.load_rom(path)?;
+        if let Some(state) = self.start_state.take() {
+            self.load_state(&state)?;
+        }
+
+        self.run()
+    }
+
+    // e.g. one saved with --dump-state-on-exit, to carry on from; memory isn't saved, so it's the
+    // ROM as loaded, and ROMs that modify themselves may not carry on as they were
+    pub fn start_from(&mut self, state: CpuState) {
+        self.start_state = Some(state);
+    }
+
+    // runs a built-in program, e.g. a demo, rather than a ROM file
+    pub fn run_program(&mut self, bytes: &[u8]) -> Result<()> {
+        self.read_sprites_into_memory()?;
+        self.load_program(bytes)?;
+
+        self.run()
+    }
 
+    fn run(&mut self) -> Result<()> {
         self.display.init()?;
         self.keyboard.init();
 
         while !self.should_quit {
-            self.cycle().unwrap_or_else(|err| {
-                self.display.exit().unwrap();
-                panic!("{:?}", err);
-            });
+            if let Err(err) = self.cycle() {
+                self.keyboard.exit();
+                self.display.exit()?;
+                self.keyboard.save_recording()?;
+                self.flush_logs()?;
+                return Err(err);
+            }
         }
 
+        self.keyboard.exit();
         self.display.exit()?;
+        self.keyboard.save_recording()?;
+        self.flush_logs()?;
+
+        self.gif_capture = None; // finishes the file
+        for path in &self.gif_paths {
+            println!("Saved GIF to {}", path.display());
+        }
+        for (addr, opcode) in &self.skipped_opcodes {
+            eprintln!("Skipped unknown opcode 0x{:04X} at 0x{:03X}", opcode, addr);
+        }
+        self.print_opcode_stats();
+
+        Ok(())
+    }
+
+    // whether the program should quit, rather than return to the menu, as the user asked to, or
+    // an instruction or frame limit was reached
+    pub fn exit_requested(&self) -> bool {
+        self.keyboard.ctrl_c_pressed || self.limit_reached
+    }
+
+    pub fn state(&self) -> CpuState {
+        CpuState {
+            pc: self.pc,
+            i: self.i,
+            v: self.v,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            stack: self.stack.clone(),
+            cols: self.display.cols(),
+            framebuffer: self.display.blocks().to_vec(),
+        }
+    }
+
+    // a state that doesn't fit this machine is rejected, rather than leaving it half loaded
+    fn load_state(&mut self, state: &CpuState) -> Result<()> {
+        if state.stack.len() > STACK_DEPTH {
+            return Err(Error::State(format!(
+                "{} subroutines on the stack, more than {}",
+                state.stack.len(),
+                STACK_DEPTH
+            )));
+        }
+        if (state.cols, state.framebuffer.len())
+            != (self.display.cols(), self.display.blocks().len())
+        {
+            return Err(Error::State(format!(
+                "the framebuffer is {}x{}, but the display is {}x{}",
+                state.cols,
+                state.framebuffer.len() / state.cols,
+                self.display.cols(),
+                self.display.rows()
+            )));
+        }
+        if state.pc as usize >= self.memory.len() {
+            return Err(Error::State(format!(
+                "pc 0x{:X} is past the end of memory",
+                state.pc
+            )));
+        }
+
+        self.pc = state.pc;
+        self.i = state.i;
+        self.v = state.v;
+        self.delay_timer = state.delay_timer;
+        self.set_sound_timer(state.sound_timer);
+        self.stack = state.stack.clone();
+        self.display.set_blocks(&state.framebuffer);
 
         Ok(())
     }
 
-    pub fn read_sprites_into_memory(&mut self) {
+    pub fn trace_to(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
+    pub fn timing_log_to(&mut self, timing_log: TimingLog) {
+        self.timing_log = Some(timing_log);
+    }
+
+    pub fn collision_log_to(&mut self, collision_log: CollisionLog) {
+        self.collision_log = Some(collision_log);
+    }
+
+    fn flush_logs(&mut self) -> Result<()> {
+        if let Some(trace) = &mut self.trace {
+            trace.flush()?;
+        }
+        if let Some(collision_log) = &mut self.collision_log {
+            collision_log.flush()?;
+        }
+        match &mut self.timing_log {
+            Some(timing_log) => Ok(timing_log.flush()?),
+            None => Ok(()),
+        }
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.should_quit
+    }
+
+    pub fn print_opcode_stats(&self) {
+        if let Some(stats) = &self.opcode_stats {
+            stats.print();
+        }
+    }
+
+    // lets the front end play sound, without the CPU depending on an audio backend
+    pub fn on_sound(&mut self, callback: impl FnMut(bool) + 'static) {
+        self.on_sound = Some(Box::new(callback));
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn read_sprites_into_memory(&mut self) -> Result<()> {
         // load into interpreter area of memory, starting at 0x000
-        self.memory[..SPRITE_BYTES.len()].copy_from_slice(&SPRITE_BYTES[..]);
+        match &self.font_path {
+            Some(path) => {
+                let font_bytes = fs::read(path)?;
+                if font_bytes.len() != SPRITE_BYTES.len() {
+                    return Err(Error::Io(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Font file must contain {} bytes (16 sprites of 5 bytes)",
+                            SPRITE_BYTES.len()
+                        ),
+                    )));
+                }
+                self.memory[..SPRITE_BYTES.len()].copy_from_slice(&font_bytes);
+            }
+            None => self.memory[..SPRITE_BYTES.len()].copy_from_slice(&SPRITE_BYTES[..]),
+        }
+        self.memory[BIG_SPRITES_ADDR..BIG_SPRITES_ADDR + BIG_SPRITE_BYTES.len()]
+            .copy_from_slice(&BIG_SPRITE_BYTES[..]);
+
+        Ok(())
     }
 
-    pub fn load_rom(&mut self, path: std::path::PathBuf) -> Result<(), io::Error> {
-        let file_bytes = fs::read(path)?;
+    // flags are persisted per ROM, by file name
+    fn load_flags(&mut self, rom_path: &std::path::Path) -> Result<()> {
+        let rom_name = rom_path.file_name().unwrap_or_default().to_string_lossy();
+        self.flags_path =
+            settings::app_config_path("flags").map(|dir| dir.join(format!("{}.flags", rom_name)));
 
-        let start_addr = 0x200;
-        self.memory[start_addr..start_addr + file_bytes.len()].copy_from_slice(&file_bytes);
+        if let Some(path) = &self.flags_path {
+            match fs::read(path) {
+                Ok(bytes) => {
+                    let len = cmp::min(bytes.len(), self.flags.len());
+                    self.flags[..len].copy_from_slice(&bytes[..len]);
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save_flags(&self) -> Result<()> {
+        if let Some(path) = &self.flags_path {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(path, self.flags)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn load_rom(&mut self, path: std::path::PathBuf) -> Result<()> {
+        let file_bytes = rom::read(&path, self.load_addr)?;
+        self.load_program(&file_bytes)
+    }
+
+    // loads a program from memory, e.g. one assembled inline, at the load address
+    pub fn load_program(&mut self, bytes: &[u8]) -> Result<()> {
+        let start_addr = self.load_addr as usize;
+        if start_addr + bytes.len() > self.memory.len() {
+            return Err(Error::RomTooLarge {
+                len: bytes.len(),
+                load_addr: self.load_addr,
+            });
+        }
+        self.memory[start_addr..start_addr + bytes.len()].copy_from_slice(bytes);
+        self.program = bytes.to_vec();
 
         Ok(())
     }
 
-    fn cycle(&mut self) -> Result<(), io::Error> {
+    // restarts the program, but leaves memory as it is, e.g. to rerun self-modified code
+    pub fn warm_reset(&mut self) {
+        self.fill_registers();
+        self.i = 0;
+        self.delay_timer = 0;
+        self.set_sound_timer(0);
+        self.pc = self.load_addr;
+        self.stack.clear();
+        self.next_key_params = None;
+        self.display.clear();
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.clear(); // there's no stepping back past a reset
+        }
+    }
+
+    // restarts the program as if just launched, with memory cleared, and the font and ROM reloaded
+    pub fn cold_reset(&mut self) -> Result<()> {
+        self.warm_reset();
+        self.fill_memory();
+        self.read_sprites_into_memory()?;
+        let program = std::mem::take(&mut self.program);
+        self.load_program(&program)
+    }
+
+    fn cycle(&mut self) -> Result<()> {
         self.last_tick = Instant::now();
 
-        for _ in 0..self.speed {
+        self.handle_debug_keys()?;
+        // checked first too, so limits of 0 stop before anything runs
+        if self.limit_reached() {
+            self.should_quit = true;
+            return Ok(());
+        }
+        let ran_frame = if self.debug_halted {
+            self.run_debug_step()?
+        } else {
+            self.run_frame()?;
+            true
+        };
+        if self.should_quit {
+            return Ok(());
+        }
+        let executed_at = Instant::now();
+
+        // the next instruction, so stepping doesn't need a separate disassembly
+        let opcode = self.opcode_at(self.pc as usize);
+        let halted_status = format!(
+            "HALTED  FRAME {}  {:03X}: {:04X} {}",
+            self.frame,
+            self.pc,
+            opcode,
+            disasm::disassemble(opcode)
+        );
+        self.display.set_debug_status(
+            match (
+                self.keyboard.debug_prompt(),
+                self.debug_halted,
+                &self.debug_message,
+            ) {
+                (Some(prompt), _, _) => Some(format!("> {}_", prompt)),
+                (None, true, Some(message)) => Some(format!("{}  {}", halted_status, message)),
+                (None, true, None) => Some(halted_status),
+                (None, false, message) => message.clone(),
+            },
+        );
+
+        self.update_sound();
+        self.display.set_sound_on(self.sound_timer > 0);
+        self.display.set_turbo(self.keyboard.turbo_toggle_on);
+        self.display.set_quit_prompt(self.keyboard.quit_prompt_on());
+        self.display.set_muted(self.keyboard.muted);
+        self.display.set_inverted(self.keyboard.inverted);
+        self.display.set_scanlines(self.keyboard.scanlines);
+        if let Some(draw_rate) = &self.draw_rate {
+            self.display.set_draw_rate(draw_rate.label());
+        }
+        self.update_gif_capture()?;
+        self.display.set_key_map_hud(self.keyboard.hud_toggle_on);
+        // turbo renders as often as usual, skipping the frames run in between
+        let speedup = if self.keyboard.turbo_toggle_on {
+            TURBO_SPEEDUP
+        } else {
+            1
+        };
+        if self.frames_to_skip > 0 {
+            self.frames_to_skip -= 1;
+        } else {
+            self.frames_to_skip = (self.frame_skip + 1) * speedup - 1;
+            if self.keyboard.help_on {
+                self.display.render_help(&self.keyboard.hotkeys())?;
+            } else if self.keyboard.quirks_on {
+                self.display.render_quirks(&self.quirk_settings())?;
+            } else if self.keyboard.pause_toggle_on {
+                let pressed = (0..0x10)
+                    .map(|key| self.keyboard.is_key_pressed(key))
+                    .collect::<Vec<_>>();
+                self.display.render_key_map(&pressed)?;
+            } else {
+                self.display.render()?;
+            }
+        }
+
+        let rendered_at = Instant::now();
+
+        // maintain 60 FPS, or a multiple of it in turbo
+        let timeout = (FPS_INTERVAL / speedup as u32)
+            .checked_sub(self.last_tick.elapsed())
+            .unwrap_or(Duration::from_secs(0));
+        if !timeout.is_zero() {
+            std::thread::sleep(timeout);
+        }
+
+        if let Some(timing_log) = &mut self.timing_log {
+            // the sleep is measured, rather than the timeout, as it can overshoot
+            let timing = FrameTiming {
+                execute: executed_at - self.last_tick,
+                render: rendered_at - executed_at,
+                sleep: rendered_at.elapsed(),
+                total: self.last_tick.elapsed(),
+            };
+            timing_log.log(self.frame, &timing)?;
+        }
+
+        if ran_frame {
+            self.frame += 1;
+            self.keyboard.set_frame(self.frame());
+            if let Some(draw_rate) = &mut self.draw_rate {
+                draw_rate.end_frame();
+            }
+        }
+        if self.limit_reached() {
+            self.should_quit = true;
+        }
+
+        Ok(())
+    }
+
+    // how the ambiguous parts of CHIP-8 are interpreted, as set by options, ROM options files and
+    // the variant, including those fixed in this interpreter, for the quirks overlay
+    fn quirk_settings(&self) -> Vec<(&'static str, String)> {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        vec![
+            ("display wait", on_off(self.quirks.display_wait)),
+            (
+                "jump (BNNN)",
+                if self.quirks.jump_vx {
+                    "to XNN + VX"
+                } else {
+                    "to NNN + V0"
+                }
+                .to_string(),
+            ),
+            ("shift", "VX in place".to_string()),
+            ("load / store", "I unchanged".to_string()),
+            ("sprites at edges", "clipped".to_string()),
+            ("vip timing", on_off(self.vip_timing)),
+            (
+                "speed",
+                format!("{} instructions/s", self.instructions_per_second),
+            ),
+            ("memory", format!("{} bytes", self.memory.len())),
+            ("load address", format!("0x{:03X}", self.load_addr)),
+            (
+                "unknown opcodes",
+                match self.on_unknown {
+                    UnknownOpcodeBehavior::Skip => "skip",
+                    UnknownOpcodeBehavior::Halt => "halt",
+                }
+                .to_string(),
+            ),
+            (
+                "RET, empty stack",
+                match self.on_empty_ret {
+                    EmptyStackBehavior::Error => "error",
+                    EmptyStackBehavior::Ignore => "ignore",
+                    EmptyStackBehavior::Halt => "halt",
+                }
+                .to_string(),
+            ),
+            (
+                "BCD past memory",
+                match self.on_bcd_overflow {
+                    MemoryOverflowBehavior::Wrap => "wrap",
+                    MemoryOverflowBehavior::Error => "error",
+                }
+                .to_string(),
+            ),
+        ]
+    }
+
+    // starts or stops recording a GIF, as toggled, and adds the current frame if recording
+    fn update_gif_capture(&mut self) -> Result<()> {
+        match (&self.gif_capture, self.keyboard.gif_toggle_on) {
+            (None, true) => {
+                let capture = GifCapture::create(
+                    std::path::Path::new("recordings"),
+                    self.display.cols(),
+                    self.display.rows(),
+                    self.display.color(),
+                    self.display.inverted(),
+                )?;
+                self.gif_paths.push(capture.path().to_path_buf());
+                self.gif_capture = Some(capture);
+            }
+            (Some(_), false) => self.gif_capture = None, // finishes the file
+            _ => (),
+        }
+        self.display.set_recording(self.gif_capture.is_some());
+
+        if let Some(capture) = &mut self.gif_capture {
+            capture.capture(self.display.blocks())?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_debug_keys(&mut self) -> Result<()> {
+        for key in self.keyboard.take_debug_presses() {
+            match key {
+                2 => self.display.toggle_ruler(),
+                5 => self.debug_halted = !self.debug_halted,
+                6 => {
+                    self.debug_halted = true;
+                    self.debug_step = Some(DebugStep::Frame);
+                }
+                7 => {
+                    self.debug_halted = true;
+                    self.debug_step = Some(DebugStep::Instruction);
+                }
+                4 => {
+                    self.debug_halted = true;
+                    self.debug_message = match self.step_back() {
+                        true => None,
+                        false => Some("No earlier instruction to step back to".to_string()),
+                    };
+                }
+                8 => self.dump_memory(format!("memory-{}.bin", self.frame))?,
+                9 => self.warm_reset(),
+                10 => self.cold_reset()?,
+                _ => (),
+            }
+        }
+
+        for line in self.keyboard.take_debug_commands() {
+            self.debug_message = Some(match debugger::parse_command(&line) {
+                Ok(command) => {
+                    self.run_debug_command(command);
+                    line.trim().to_string()
+                }
+                Err(err) => err,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn run_debug_command(&mut self, command: DebugCommand) {
+        match command {
+            DebugCommand::SetV(idx, val) => self.v[idx] = val,
+            DebugCommand::SetI(val) => self.i = val,
+            DebugCommand::SetPc(val) => self.pc = val,
+            DebugCommand::SetDelayTimer(val) => self.delay_timer = val,
+            DebugCommand::SetSoundTimer(val) => self.set_sound_timer(val),
+            DebugCommand::SetMemory(addr, val) => {
+                let addr = self.addr(addr as usize);
+                self.memory[addr] = val;
+            }
+        }
+    }
+
+    // reverts the last instruction run, as recorded in the undo log; returns whether there was one
+    fn step_back(&mut self) -> bool {
+        let undo = match self.undo_log.as_mut().and_then(|undo_log| undo_log.pop()) {
+            Some(undo) => undo,
+            None => return false,
+        };
+
+        for (addr, old_val) in undo.memory.into_iter().rev() {
+            self.memory[addr] = old_val;
+        }
+        for (x, y) in undo.toggled {
+            self.display.set_block(x, y); // toggles it back
+        }
+        if let Some(blocks) = undo.cleared {
+            self.display.set_blocks(&blocks);
+        }
+        if let Some(addr) = undo.popped {
+            self.stack.push(addr);
+        }
+        self.stack.truncate(undo.stack_len);
+        self.pc = undo.pc;
+        self.i = undo.i;
+        self.v = undo.v;
+        self.delay_timer = undo.delay_timer;
+        self.set_sound_timer(undo.sound_timer);
+        // not waiting for a key, even if the instruction was LD Vx, K
+        self.next_key_params = None;
+        self.paused = false;
+        self.instructions = self.instructions.saturating_sub(1);
+
+        true
+    }
+
+    // the undo record of the running instruction, if stepping back is enabled
+    fn undo_record(&mut self) -> Option<&mut Undo> {
+        self.undo_log
+            .as_mut()
+            .and_then(|undo_log| undo_log.current())
+    }
+
+    fn write_memory(&mut self, addr: usize, val: u8) {
+        let old_val = std::mem::replace(&mut self.memory[addr], val);
+        if let Some(undo) = self.undo_record() {
+            undo.memory.push((addr, old_val));
+        }
+    }
+
+    // writes all of memory, including any code the ROM has modified, as raw bytes
+    pub fn dump_memory(&self, path: impl AsRef<std::path::Path>) -> Result<()> {
+        Ok(fs::write(path, &self.memory)?)
+    }
+
+    // while halted by the debugger, input is still handled, but instructions only run when
+    // stepped; returns whether a whole frame ran
+    fn run_debug_step(&mut self) -> Result<bool> {
+        self.keyboard.process_pressed_keys();
+        if self.keyboard.esc_pressed {
+            self.should_quit = true;
+            return Ok(false);
+        }
+
+        match self.debug_step.take() {
+            Some(DebugStep::Frame) => {
+                self.run_frame()?;
+                return Ok(true);
+            }
+            Some(DebugStep::Instruction) if !self.paused => {
+                self.step()?;
+            }
+            _ => (),
+        }
+
+        Ok(false)
+    }
+
+    // runs one frame's worth of instructions, handling input, then updates the timers
+    fn run_frame(&mut self) -> Result<()> {
+        let budget = self.frame_budget();
+        let mut spent = 0;
+        while spent < budget {
             if self.next_key_params.is_some() {
                 // program paused, and waiting for next key press
                 self.process_next_key();
@@ -126,64 +875,340 @@ impl Cpu {
                 self.paused = true;
             }
 
-            if self.paused {
+            if self.timers_frozen() {
+                // re-anchored, so no time builds up to catch up on after resuming
+                self.delay_timer_checked = Instant::now();
+            } else {
+                self.tick_delay_timer_by_clock();
+            }
+            if self.timers_frozen() {
+                spent += 1;
                 continue;
             }
 
-            let opcode = ((self.memory[self.pc as usize] as u16) << 8)
-                | (self.memory[self.pc as usize + 1]) as u16;
-            self.exec_instruction(opcode);
+            let opcode = self.step()?;
+            spent += self.cycle_cost(opcode);
+            if self.limit_reached() {
+                self.should_quit = true;
+                return Ok(());
+            }
+            if self.ends_frame(opcode) || self.is_idle() {
+                break;
+            }
+        }
+        self.cycle_overrun = spent.saturating_sub(budget);
+
+        // timers tick once per emulated frame, rather than by the wall clock (unless it's enabled for
+        // the delay timer), so they freeze while paused, with no time to catch up on resume
+        if !self.timers_frozen() {
+            self.update_timers();
+        }
+
+        Ok(())
+    }
+
+    // paused by the user, or waiting for a key, with Fx0A or input advance
+    fn timers_frozen(&self) -> bool {
+        self.paused || self.awaiting_input()
+    }
+
+    // with input advance, the game, timers included, is frozen until a key is pressed, unless it's
+    // waiting for one itself, when it runs as usual to take it
+    fn awaiting_input(&self) -> bool {
+        self.input_advance && self.next_key_params.is_none() && !self.keyboard.any_key_pressed()
+    }
+
+    // runs a ROM without a display, as fast as possible, for a number of frames, passing the
+    // framebuffer to on_frame after each; input comes from keyboard playback, timed by frame
+    // rather than by the wall clock, so runs are deterministic
+    pub fn run_frames(
+        &mut self,
+        path: std::path::PathBuf,
+        frames: u64,
+        mut on_frame: impl FnMut(&[u8]),
+    ) -> Result<()> {
+        self.read_sprites_into_memory()?;
+        self.load_rom(path)?;
+        self.keyboard.use_frame_clock();
+        self.slow_draw = None; // nothing to watch
+        self.wall_clock_dt = false; // so runs are deterministic
+
+        while self.frame < frames && !self.should_quit {
+            self.run_frame()?;
+            on_frame(self.display.blocks());
+
+            self.frame += 1;
+            self.keyboard.set_frame(self.frame());
         }
 
-        if !self.paused {
+        Ok(())
+    }
+
+    // runs a ROM without a display or keyboard, as fast as possible, until the instruction limit
+    // is reached, it waits for a key press, or it halts; returns the number of instructions executed
+    pub fn run_headless(&mut self, path: std::path::PathBuf, max_instructions: u64) -> Result<u64> {
+        self.read_sprites_into_memory()?;
+        self.load_rom(path)?;
+        self.slow_draw = None; // nothing to watch
+        self.wall_clock_dt = false; // so runs are deterministic
+
+        let mut executed = 0;
+        while executed < max_instructions {
+            let budget = self.frame_budget();
+            let mut spent = 0;
+            while spent < budget {
+                if executed == max_instructions
+                    || self.next_key_params.is_some()
+                    || self.should_quit
+                {
+                    return Ok(executed);
+                }
+
+                let opcode = self.step()?;
+                executed += 1;
+                spent += self.cycle_cost(opcode);
+                if self.ends_frame(opcode) {
+                    break;
+                }
+            }
+            self.cycle_overrun = spent.saturating_sub(budget);
+
             self.update_timers();
+            self.frame += 1;
+        }
+
+        Ok(executed)
+    }
+
+    // fetches and executes the next instruction, returning its opcode
+    fn step(&mut self) -> Result<u16> {
+        let pc = self.pc as usize;
+        let opcode = self.opcode_at(pc);
+        let before = self.trace.as_ref().map(|_| Registers {
+            v: self.v,
+            i: self.i,
+            sp: self.stack.len(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        });
+        self.exec_instruction(opcode)?;
+        self.instructions += 1;
+        if let Some(idle_detector) = &mut self.idle_detector {
+            idle_detector.observe(pc as u16, opcode, &self.v, self.i, self.stack.len());
+        }
+
+        if let (Some(trace), Some(before)) = (&mut self.trace, before) {
+            trace.log(pc as u16, opcode, &before, &self.trace_notes)?;
         }
+        self.trace_notes.clear();
+
+        Ok(opcode)
+    }
+
+    // ROMs shouldn't write over the font and interpreter area, below the load address, so such
+    // writes are noted in the trace, as they're usually pointer bugs
+    fn note_low_write(&mut self, start_addr: usize, len: usize) {
+        if self.trace.is_none() {
+            return;
+        }
+        if let Some(addr) = (start_addr..start_addr + len)
+            .map(|addr| self.addr(addr))
+            .find(|addr| *addr < self.load_addr as usize)
+        {
+            self.trace_notes.push(format!("low_write=0x{:03X}", addr));
+        }
+    }
+
+    fn opcode_at(&self, addr: usize) -> u16 {
+        ((self.memory[self.addr(addr)] as u16) << 8) | self.memory[self.addr(addr + 1)] as u16
+    }
+
+    // wraps an address around the end of memory
+    fn addr(&self, addr: usize) -> usize {
+        addr & (self.memory.len() - 1)
+    }
+
+    // instructions per frame, plus one whenever the fractions left over add up to a whole, so at 700
+    // per second, frames run 11 or 12, and 700 over a second; or VIP cycles, carrying over any
+    // overrun from the last frame
+    fn frame_budget(&mut self) -> u32 {
+        if self.vip_timing {
+            return VIP_CYCLES_PER_FRAME.saturating_sub(self.cycle_overrun);
+        }
+        self.instruction_carry += self.instructions_per_second % FPS;
+        let extra = self.instruction_carry / FPS;
+        self.instruction_carry %= FPS;
+        self.instructions_per_frame + extra
+    }
 
-        if self.keyboard.pause_toggle_on {
-            self.display.render_key_map()?;
+    fn cycle_cost(&self, opcode: u16) -> u32 {
+        if self.vip_timing {
+            vip_cycles(opcode)
         } else {
-            self.display.render()?;
+            1
         }
+    }
 
-        // maintain 60 FPS
-        let timeout = FPS_INTERVAL
-            .checked_sub(self.last_tick.elapsed())
-            .unwrap_or(Duration::from_secs(0));
-        if !timeout.is_zero() {
-            std::thread::sleep(timeout);
+    fn ends_frame(&self, opcode: u16) -> bool {
+        // DRW waits for the vertical blank, so no more instructions run this frame
+        self.quirks.display_wait && opcode & 0xF000 == 0xD000
+    }
+
+    // whether --cycles or --frames has been reached, noting it, so the program exits
+    fn limit_reached(&mut self) -> bool {
+        let reached = |count: u64, max: Option<u64>| max.map_or(false, |max| count >= max);
+        if reached(self.instructions, self.max_instructions) || reached(self.frame, self.max_frames)
+        {
+            self.limit_reached = true;
         }
+        self.limit_reached
+    }
 
-        Ok(())
+    fn is_idle(&mut self) -> bool {
+        self.idle_detector
+            .as_mut()
+            .map_or(false, |idle_detector| idle_detector.take_idle())
     }
 
     fn process_next_key(&mut self) {
         let params = self
             .next_key_params
-            .as_ref()
+            .as_mut()
             .expect("Attempt to process next key without setting next_key_params");
 
-        if let Some(val) = self.keyboard.get_next_key(params.valid_after) {
-            if self.keyboard.esc_pressed || self.keyboard.pause_toggle_on {
-                return;
+        match params.pressed_key {
+            None => {
+                if let Some(val) = self.keyboard.get_next_key(params.valid_after) {
+                    if self.keyboard.esc_pressed || self.keyboard.pause_toggle_on {
+                        return;
+                    }
+
+                    params.pressed_key = Some(val);
+                }
+            }
+            Some(val) => {
+                // terminals don't report key releases, so the key counts as released once it's
+                // no longer being held (repeated)
+                self.keyboard.process_pressed_keys();
+                if self.keyboard.esc_pressed || self.keyboard.pause_toggle_on {
+                    return;
+                }
+
+                if !self.keyboard.is_key_pressed(val) {
+                    self.v[params.destination_idx] = val;
+                    self.paused = false;
+                    self.next_key_params = None;
+                }
             }
+        }
+    }
+
+    // with the wall clock option, counts the delay timer down as time passes while instructions
+    // run, so a ROM polling it sees it change mid-frame, rather than once at the end of a frame
+    fn tick_delay_timer_by_clock(&mut self) {
+        if !self.wall_clock_dt {
+            return;
+        }
+        let now = Instant::now();
+        let gap = now - self.delay_timer_checked;
+        self.delay_timer_checked = now;
+        if gap > DELAY_TIMER_MAX_GAP {
+            return; // the timer stood still, e.g. while halted by the debugger
+        }
 
-            self.v[params.destination_idx] = val;
-            self.paused = false;
-            self.next_key_params = None;
+        self.delay_timer_elapsed += gap;
+        while self.delay_timer_elapsed >= DELAY_TIMER_INTERVAL {
+            self.delay_timer_elapsed -= DELAY_TIMER_INTERVAL;
+            self.delay_timer = self.delay_timer.saturating_sub(1);
         }
     }
 
     fn update_timers(&mut self) {
-        if self.delay_timer > 0 {
+        if self.delay_timer > 0 && !self.wall_clock_dt {
             self.delay_timer -= 1;
         }
         if self.sound_timer > 0 {
-            self.sound_timer -= 1;
+            self.set_sound_timer(self.sound_timer - 1);
         }
     }
 
-    fn exec_instruction(&mut self, opcode: u16) {
-        self.pc += 2;
+    fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+        self.update_sound();
+    }
+
+    // starts or stops the sound as the sound timer does, but keeps it off in turbo, where short
+    // sounds would run together into a drone, and back on if still due when turbo ends
+    fn update_sound(&mut self) {
+        let playing = self.sound_timer > 0 && !self.keyboard.turbo_toggle_on;
+        if playing == self.sound_playing {
+            return;
+        }
+        self.sound_playing = playing;
+        if !self.keyboard.muted {
+            if let Some(callback) = &mut self.on_sound {
+                callback(playing);
+            }
+        }
+    }
+
+    // where the current instruction is, for error messages, e.g. "0x00EE at 0x2A4 (I = 0x300)"
+    fn crash_context(&self, opcode: u16) -> String {
+        format!(
+            "0x{:04X} at 0x{:03X} (I = 0x{:03X})",
+            opcode,
+            self.pc.wrapping_sub(2), // already incremented
+            self.i
+        )
+    }
+
+    fn skip_next(&mut self, opcode: u16) -> Result<()> {
+        match self.pc.checked_add(2) {
+            Some(pc) => self.pc = pc,
+            None => return Err(Error::PcOutOfBounds(self.crash_context(opcode))),
+        }
+        Ok(())
+    }
+
+    fn unknown_opcode(&mut self, opcode: u16) -> Result<()> {
+        let addr = self.pc - 2;
+
+        match self.on_unknown {
+            UnknownOpcodeBehavior::Skip => {
+                self.skipped_opcodes.insert((addr, opcode));
+                Ok(())
+            }
+            UnknownOpcodeBehavior::Halt => Err(Error::UnknownOpcode(self.crash_context(opcode))),
+        }
+    }
+
+    fn exec_instruction(&mut self, opcode: u16) -> Result<()> {
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.push(Undo {
+                pc: self.pc,
+                i: self.i,
+                v: self.v,
+                delay_timer: self.delay_timer,
+                sound_timer: self.sound_timer,
+                stack_len: self.stack.len(),
+                popped: None,
+                memory: vec![],
+                toggled: vec![],
+                cleared: None,
+            });
+        }
+        // crash_context expects the PC past the instruction, so it's incremented before checking
+        let (pc, overflowed) = self.pc.overflowing_add(2);
+        self.pc = pc;
+        if overflowed {
+            return Err(Error::PcOutOfBounds(self.crash_context(opcode)));
+        }
+        if let Some(stats) = &mut self.opcode_stats {
+            stats.record(opcode);
+        }
+        if let Some(draw_rate) = &mut self.draw_rate {
+            draw_rate.record(opcode);
+        }
 
         let x = (opcode as usize & 0x0F00) >> 8;
         let y = (opcode as usize & 0x00F0) >> 4;
@@ -193,6 +1218,12 @@ impl Cpu {
                 // CLS
                 0x00E0 => {
                     // Clear the display.
+                    if self.undo_log.is_some() {
+                        let blocks = self.display.blocks().to_vec();
+                        if let Some(undo) = self.undo_record() {
+                            undo.cleared = Some(blocks);
+                        }
+                    }
                     self.display.clear();
                 }
                 // RET
@@ -201,8 +1232,19 @@ impl Cpu {
                     // The interpreter sets the program counter to the address at the top of the
                     // stack, then subtracts 1 from the stack pointer.
                     match self.stack.pop() {
-                        Some(addr) => self.pc = addr,
-                        None => panic!("Attempt to pop from empty stack"),
+                        Some(addr) => {
+                            self.pc = addr;
+                            if let Some(undo) = self.undo_record() {
+                                undo.popped = Some(addr);
+                            }
+                        }
+                        None => match self.on_empty_ret {
+                            EmptyStackBehavior::Error => {
+                                return Err(Error::StackUnderflow(self.crash_context(opcode)))
+                            }
+                            EmptyStackBehavior::Ignore => (),
+                            EmptyStackBehavior::Halt => self.should_quit = true,
+                        },
                     }
                 }
                 // 0nnn - SYS addr
@@ -217,13 +1259,20 @@ impl Cpu {
             0x1000 => {
                 // Jump to location nnn.
                 // The interpreter sets the program counter to nnn.
-                self.pc = opcode & 0xFFF;
+                let addr = opcode & 0xFFF;
+                if self.halt_on_self_jump && addr == self.pc - 2 {
+                    self.should_quit = true;
+                }
+                self.pc = addr;
             }
             // CALL addr
             0x2000 => {
                 // Call subroutine at nnn.
                 // The interpreter increments the stack pointer, then puts the current PC on the top
                 // of the stack. The PC is then set to nnn.
+                if self.stack.len() == STACK_DEPTH {
+                    return Err(Error::StackOverflow(self.crash_context(opcode)));
+                }
                 self.stack.push(self.pc);
                 self.pc = opcode & 0xFFF;
             }
@@ -233,7 +1282,7 @@ impl Cpu {
                 // The interpreter compares register Vx to kk, and if they are equal, increments the
                 // program counter by 2.
                 if self.v[x] == opcode as u8 {
-                    self.pc += 2;
+                    self.skip_next(opcode)?;
                 }
             }
             // SNE Vx, byte
@@ -242,7 +1291,7 @@ impl Cpu {
                 // The interpreter compares register Vx to kk, and if they are not equal, increments
                 // the program counter by 2.
                 if self.v[x] != opcode as u8 {
-                    self.pc += 2;
+                    self.skip_next(opcode)?;
                 }
             }
             // SE Vx, Vy
@@ -251,7 +1300,7 @@ impl Cpu {
                 // The interpreter compares register Vx to register Vy, and if they are equal,
                 // increments the program counter by 2.
                 if self.v[x] == self.v[y] {
-                    self.pc += 2;
+                    self.skip_next(opcode)?;
                 }
             }
             // LD Vx, byte
@@ -264,7 +1313,7 @@ impl Cpu {
             0x7000 => {
                 // Set Vx = Vx + kk.
                 // Adds the value kk to the value of register Vx, then stores the result in Vx.
-                self.v[x] = (self.v[x] as u16 + opcode) as u8;
+                self.v[x] = self.v[x].wrapping_add(opcode as u8);
             }
             0x8000 => {
                 match opcode & 0xF {
@@ -316,34 +1365,39 @@ impl Cpu {
                         // Set Vx = Vx - Vy, set VF = NOT borrow.
                         // If Vx > Vy, then VF is set to 1, otherwise 0. Then Vy is subtracted from
                         // Vx, and the results stored in Vx.
-                        self.v[0xF] = if self.v[x] > self.v[y] { 1 } else { 0 };
-                        self.v[x] = self.v[x].overflowing_sub(self.v[y]).0;
+                        // the flag is written last, so it wins when Vx is VF, as with all of these
+                        let not_borrow = if self.v[x] > self.v[y] { 1 } else { 0 };
+                        self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+                        self.v[0xF] = not_borrow;
                     }
                     // SHR Vx {, Vy}
                     0x6 => {
                         // Set Vx = Vx SHR 1.
                         // If the least-significant bit of Vx is 1, then VF is set to 1, otherwise
                         // 0. Then Vx is divided by 2.
-                        self.v[0xF] = self.v[x] & 0x1;
+                        let lsb = self.v[x] & 0x1;
                         self.v[x] >>= 1;
+                        self.v[0xF] = lsb;
                     }
                     // SUBN Vx, Vy
                     0x7 => {
                         // Set Vx = Vy - Vx, set VF = NOT borrow.
                         // If Vy > Vx, then VF is set to 1, otherwise 0. Then Vx is subtracted from
                         // Vy, and the results stored in Vx.
-                        self.v[0xF] = if self.v[y] > self.v[x] { 1 } else { 0 };
-                        self.v[x] = self.v[y].overflowing_sub(self.v[x]).0;
+                        let not_borrow = if self.v[y] > self.v[x] { 1 } else { 0 };
+                        self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                        self.v[0xF] = not_borrow;
                     }
                     // SHL Vx {, Vy}
                     0xE => {
                         // Set Vx = Vx SHL 1.
                         // If the most-significant bit of Vx is 1, then VF is set to 1, otherwise
                         // to 0. Then Vx is multiplied by 2.
-                        self.v[0xF] = self.v[x] & 0x80;
+                        let msb = self.v[x] >> 7;
                         self.v[x] <<= 1;
+                        self.v[0xF] = msb;
                     }
-                    _ => (),
+                    _ => return self.unknown_opcode(opcode),
                 }
             }
             // SNE Vx, Vy
@@ -352,7 +1406,7 @@ impl Cpu {
                 // The values of Vx and Vy are compared, and if they are not equal, the program
                 // counter is increased by 2.
                 if self.v[x] != self.v[y] {
-                    self.pc += 2;
+                    self.skip_next(opcode)?;
                 }
             }
             // LD I, addr
@@ -365,7 +1419,13 @@ impl Cpu {
             0xB000 => {
                 // Jump to location nnn + V0.
                 // The program counter is set to nnn plus the value of V0.
-                self.pc = (opcode & 0xFFF) + self.v[0x0] as u16;
+                let offset = if self.quirks.jump_vx {
+                    self.v[x]
+                } else {
+                    self.v[0x0]
+                };
+                // masked to the 12-bit address space, as nnn + V0 can pass 0xFFF
+                self.pc = ((opcode & 0xFFF) + offset as u16) & 0xFFF;
             }
             // RND Vx, byte
             0xC000 => {
@@ -373,7 +1433,7 @@ impl Cpu {
                 // The interpreter generates a random number from 0 to 255, which is then ANDed with
                 // the value kk. The results are stored in Vx. See instruction 8xy2 for more
                 // information on AND.
-                self.v[x] = rand::thread_rng().gen_range(0..=0xFF) & opcode as u8;
+                self.v[x] = self.rng.gen_range(0..=0xFF) & opcode as u8;
             }
             // DRW Vx, Vy, nibble
             0xD000 => {
@@ -389,29 +1449,45 @@ impl Cpu {
                 // screen and sprites.
                 let sprite_byte_len = opcode & 0xF;
                 let start_addr = self.i as usize;
-                let x_start = self.v[x] as u16 % COLS as u16;
-                let y_start = self.v[y] as u16 % ROWS as u16;
-                let max_width = COLS as u16 - x_start;
-                let max_height = ROWS as u16 - y_start;
+                let (cols, rows) = (self.display.cols() as u16, self.display.rows() as u16);
+                let x_start = self.v[x] as u16 % cols;
+                let y_start = self.v[y] as u16 % rows;
+                let max_width = cols - x_start;
+                let max_height = rows - y_start;
 
-                self.v[0xF] = 0;
+                let mut erased = 0;
 
                 for row in 0..cmp::min(sprite_byte_len, max_height) {
-                    let mut sprite_row = self.memory[start_addr + row as usize];
+                    let mut sprite_row = self.memory[self.addr(start_addr + row as usize)];
 
                     for col in 0..cmp::min(8, max_width) {
                         // check if leftmost bit, representing current block is set
                         if sprite_row & 0x80 > 0 {
-                            let has_collision =
-                                self.display.set_block(x_start + col, y_start + row);
-                            if has_collision {
-                                self.v[0xF] = 1;
+                            let block = (x_start + col, y_start + row);
+                            let has_collision = self.display.set_block(block.0, block.1);
+                            erased += has_collision as u16;
+                            if let Some(undo) = self.undo_record() {
+                                undo.toggled.push(block);
+                            }
+
+                            if let Some(delay) = self.slow_draw {
+                                self.display.render()?;
+                                std::thread::sleep(delay);
                             }
                         }
 
                         sprite_row <<= 1; // shift next bit into leftmost position
                     }
                 }
+
+                self.v[0xF] = if erased > 0 { 1 } else { 0 };
+                if self.trace.is_some() {
+                    self.trace_notes.push(format!("erased={}", erased));
+                }
+                if let (Some(collision_log), true) = (&mut self.collision_log, erased > 0) {
+                    let pos = (x_start, y_start);
+                    collision_log.log(self.frame, self.pc - 2, self.i, pos, erased)?;
+                }
             }
             0xE000 => match opcode & 0xFF {
                 // SKP Vx
@@ -421,7 +1497,7 @@ impl Cpu {
                     // currently in the down position, PC is increased by 2.
                     let key_val = self.v[x];
                     if self.keyboard.is_key_pressed(key_val) {
-                        self.pc += 2;
+                        self.skip_next(opcode)?;
                     }
                 }
                 // SKNP Vx
@@ -431,10 +1507,10 @@ impl Cpu {
                     // currently in the up position, PC is increased by 2.
                     let key_val = self.v[x];
                     if !self.keyboard.is_key_pressed(key_val) {
-                        self.pc += 2;
+                        self.skip_next(opcode)?;
                     }
                 }
-                _ => (),
+                _ => return self.unknown_opcode(opcode),
             },
             0xF000 => match opcode & 0xFF {
                 // LD Vx, DT
@@ -448,9 +1524,12 @@ impl Cpu {
                     // Wait for a key press, store the value of the key in Vx.
                     // All execution stops until a key is pressed, then the value of that key is
                     // stored in Vx.
+                    // As on the COSMAC VIP, the key must be pressed after this instruction, and is
+                    // only stored once it's released.
                     self.next_key_params = Some(NextKeyParams {
                         destination_idx: x as usize,
-                        valid_after: Instant::now(),
+                        valid_after: self.keyboard.now(),
+                        pressed_key: None,
                     });
                     self.paused = true;
                 }
@@ -464,7 +1543,7 @@ impl Cpu {
                 0x18 => {
                     // Set sound timer = Vx.
                     // ST is set equal to the value of Vx.
-                    self.sound_timer = self.v[x];
+                    self.set_sound_timer(self.v[x]);
                 }
                 // ADD I, Vx
                 0x1E => {
@@ -480,6 +1559,12 @@ impl Cpu {
                     // information on the Chip-8 hexadecimal font.
                     self.i = self.v[x] as u16 * 5; // hex sprites are 5 bytes each
                 }
+                // LD HF, Vx
+                0x30 => {
+                    // Set I = location of the big sprite for digit Vx. (SUPER-CHIP)
+                    let offset = (self.v[x] & 0xF) as usize * 10; // big sprites are 10 bytes each
+                    self.i = (BIG_SPRITES_ADDR + offset) as u16;
+                }
                 // LD B, Vx
                 0x33 => {
                     // Store BCD representation of Vx in memory locations I, I+1, and I+2.
@@ -487,9 +1572,16 @@ impl Cpu {
                     // in memory at location in I, the tens digit at location I+1, and the ones
                     // digit at location I+2.
                     let idx = self.i as usize;
-                    self.memory[idx] = self.v[x] / 100;
-                    self.memory[idx + 1] = (self.v[x] % 100) / 10;
-                    self.memory[idx + 2] = self.v[x] % 10;
+                    if idx + 3 > self.memory.len()
+                        && self.on_bcd_overflow == MemoryOverflowBehavior::Error
+                    {
+                        return Err(Error::MemoryOverflow(self.crash_context(opcode)));
+                    }
+                    self.note_low_write(idx, 3);
+                    for (offset, digit) in bcd(self.v[x]).into_iter().enumerate() {
+                        let addr = self.addr(idx + offset);
+                        self.write_memory(addr, digit);
+                    }
                 }
                 // LD [I], Vx
                 0x55 => {
@@ -497,7 +1589,11 @@ impl Cpu {
                     // The interpreter copies the values of registers V0 through Vx into memory,
                     // starting at the address in I.
                     let start_addr = self.i as usize;
-                    self.memory[start_addr..=start_addr + x].copy_from_slice(&self.v[0x0..=x])
+                    self.note_low_write(start_addr, x + 1);
+                    for offset in 0..=x {
+                        let addr = self.addr(start_addr + offset);
+                        self.write_memory(addr, self.v[offset]);
+                    }
                 }
                 // LD Vx, [I]
                 0x65 => {
@@ -505,11 +1601,372 @@ impl Cpu {
                     // The interpreter reads values from memory starting at location I into
                     // registers V0 through Vx.
                     let start_addr = self.i as usize;
-                    self.v[0x0..=x].copy_from_slice(&self.memory[start_addr..=start_addr + x])
+                    let mask = self.memory.len() - 1;
+                    for (offset, val) in self.v[0x0..=x].iter_mut().enumerate() {
+                        *val = self.memory[(start_addr + offset) & mask];
+                    }
                 }
-                _ => (),
+                // LD R, Vx
+                0x75 => {
+                    // Store registers V0 through Vx in the RPL user flags (x <= 7). (SUPER-CHIP)
+                    let n = cmp::min(x, 7);
+                    self.flags[0x0..=n].copy_from_slice(&self.v[0x0..=n]);
+                    self.save_flags()?;
+                }
+                // LD Vx, R
+                0x85 => {
+                    // Read registers V0 through Vx from the RPL user flags (x <= 7). (SUPER-CHIP)
+                    let n = cmp::min(x, 7);
+                    self.v[0x0..=n].copy_from_slice(&self.flags[0x0..=n]);
+                }
+                _ => return self.unknown_opcode(opcode),
             },
-            _ => panic!("Unknown opcode 0x{:X}", opcode),
+            _ => return self.unknown_opcode(opcode),
         }
+
+        Ok(())
+    }
+}
+
+// approximate machine cycles taken by the COSMAC VIP interpreter, ignoring the few extra taken by
+// skips and the delays of DRW waiting for the display
+fn vip_cycles(opcode: u16) -> u32 {
+    let x = ((opcode & 0x0F00) >> 8) as u32;
+    let n = (opcode & 0x000F) as u32;
+    match (opcode & 0xF000, opcode & 0x00FF) {
+        (0x0000, 0xE0) => 24,
+        (0x0000, 0xEE) => 10,
+        (0x1000, _) | (0xA000, _) => 12,
+        (0x2000, _) => 26,
+        (0x3000, _) | (0x4000, _) | (0x7000, _) => 10,
+        (0x5000, _) | (0x9000, _) | (0xE000, _) => 14,
+        (0x6000, _) => 6,
+        (0x8000, _) => 44,
+        (0xB000, _) => 22,
+        (0xC000, _) => 36,
+        (0xD000, _) => 22 + 46 * n,
+        (0xF000, 0x0A) => 19,
+        (0xF000, 0x1E) => 16,
+        (0xF000, 0x29) => 20,
+        (0xF000, 0x33) => 100,
+        (0xF000, 0x55) | (0xF000, 0x65) => 14 + 14 * (x + 1),
+        _ => 10,
+    }
+}
+
+// whether exec_instruction handles an opcode, rather than ignoring it (SYS) or treating it as
+// unknown; keep in sync with exec_instruction
+pub fn is_implemented(opcode: u16) -> bool {
+    match opcode & 0xF000 {
+        0x0000 => matches!(opcode, 0x00E0 | 0x00EE),
+        0x8000 => matches!(opcode & 0xF, 0x0..=0x7 | 0xE),
+        0xE000 => matches!(opcode & 0xFF, 0x9E | 0xA1),
+        0xF000 => matches!(
+            opcode & 0xFF,
+            0x07 | 0x0A | 0x15 | 0x18 | 0x1E | 0x29 | 0x30 | 0x33 | 0x55 | 0x65 | 0x75 | 0x85
+        ),
+        _ => true,
+    }
+}
+
+// binary-coded decimal digits of a byte: [hundreds, tens, ones]
+pub fn bcd(value: u8) -> [u8; 3] {
+    [value / 100, (value % 100) / 10, value % 10]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::{COLS, ROWS};
+
+    fn test_cpu() -> Cpu {
+        let options = Options::new();
+        let keyboard = Keyboard::new(options.key_ttl, options.arrow_keys, options.pause_key);
+        Cpu::new(Display::new(&options), keyboard, &options)
+    }
+
+    #[test]
+    fn add_byte_wraps_without_opcode_bits() {
+        let mut cpu = test_cpu();
+        cpu.v[0x3] = 0xFF;
+        cpu.exec_instruction(0x7302).unwrap();
+        assert_eq!(cpu.v[0x3], 0x01);
+        assert_eq!(cpu.v[0xF], 0); // no carry flag
+
+        cpu.v[0xA] = 0x00;
+        cpu.exec_instruction(0x7A10).unwrap();
+        assert_eq!(cpu.v[0xA], 0x10);
+    }
+
+    #[test]
+    fn load_program_from_slice() {
+        let mut cpu = test_cpu();
+        cpu.load_program(&[0x60, 0x0A, 0x70, 0x01]).unwrap();
+        cpu.step().unwrap();
+        cpu.step().unwrap();
+        assert_eq!(cpu.v[0x0], 0x0B);
+        assert_eq!(cpu.pc, 0x204);
+
+        assert!(cpu.load_program(&vec![0; cpu.memory.len()]).is_err());
+    }
+
+    #[test]
+    fn warm_reset_keeps_memory_and_cold_reset_reloads() {
+        let mut cpu = test_cpu();
+        cpu.read_sprites_into_memory().unwrap();
+        cpu.load_program(&[0x60, 0x0A, 0x12, 0x02]).unwrap();
+        cpu.step().unwrap();
+        cpu.memory[0x203] = 0x00; // self-modified
+        cpu.memory[0x000] = 0x00; // font overwritten
+
+        cpu.warm_reset();
+        assert_eq!(cpu.v[0x0], 0);
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!(cpu.memory[0x203], 0x00);
+        assert_eq!(cpu.memory[0x000], 0x00);
+
+        cpu.cold_reset().unwrap();
+        assert_eq!(cpu.memory[0x203], 0x02);
+        assert_eq!(cpu.memory[0x000], SPRITE_BYTES[0]);
+    }
+
+    #[test]
+    fn init_pattern_fills_memory_and_registers() {
+        let mut options = Options::new();
+        options.init_pattern = InitPattern::Ff;
+        let keyboard = Keyboard::new(options.key_ttl, options.arrow_keys, options.pause_key);
+        let cpu = Cpu::new(Display::new(&options), keyboard, &options);
+        assert!(cpu.memory.iter().all(|byte| *byte == 0xFF));
+        assert_eq!(cpu.v, [0xFF; 0x10]);
+    }
+
+    #[test]
+    fn jump_with_offset_wraps_at_0x1000() {
+        let mut cpu = test_cpu();
+        cpu.v[0x0] = 0x10;
+        cpu.exec_instruction(0xBFF8).unwrap();
+        assert_eq!(cpu.pc, 0x008);
+
+        cpu.v[0x0] = 0x07;
+        cpu.exec_instruction(0xBFF8).unwrap();
+        assert_eq!(cpu.pc, 0xFFF);
+
+        cpu.quirks.jump_vx = true;
+        cpu.v[0x2] = 0x20;
+        cpu.exec_instruction(0xB234).unwrap();
+        assert_eq!(cpu.pc, 0x254);
+    }
+
+    #[test]
+    fn empty_stack_ret_errors_unless_lenient() {
+        let mut cpu = test_cpu();
+        let err = cpu.exec_instruction(0x00EE).unwrap_err();
+        assert_eq!(err.exit_code(), 4);
+
+        cpu.on_empty_ret = EmptyStackBehavior::Ignore;
+        cpu.pc = 0x300;
+        cpu.exec_instruction(0x00EE).unwrap();
+        assert_eq!(cpu.pc, 0x302);
+        assert!(!cpu.should_quit);
+
+        cpu.on_empty_ret = EmptyStackBehavior::Halt;
+        cpu.exec_instruction(0x00EE).unwrap();
+        assert!(cpu.should_quit);
+    }
+
+    #[test]
+    fn call_past_stack_depth_and_pc_past_end_error() {
+        let mut cpu = test_cpu();
+        for _ in 0..STACK_DEPTH {
+            cpu.exec_instruction(0x2300).unwrap();
+        }
+        let err = cpu.exec_instruction(0x2300).unwrap_err();
+        assert_eq!(err.exit_code(), 6);
+
+        cpu.pc = 0xFFFC;
+        cpu.v[0x0] = 1;
+        let err = cpu.exec_instruction(0x3001).unwrap_err();
+        assert_eq!(err.exit_code(), 7);
+    }
+
+    #[test]
+    fn bcd_store_wraps_or_errors_at_end_of_memory() {
+        let mut cpu = test_cpu();
+        let end = cpu.memory.len() - 1;
+        cpu.v[0x3] = 123;
+        cpu.i = end as u16 - 1;
+        cpu.exec_instruction(0xF333).unwrap();
+        assert_eq!(cpu.memory[end - 1..], [1, 2]);
+        assert_eq!(cpu.memory[0], 3);
+
+        cpu.on_bcd_overflow = MemoryOverflowBehavior::Error;
+        let err = cpu.exec_instruction(0xF333).unwrap_err();
+        assert_eq!(err.exit_code(), 5);
+
+        cpu.i = end as u16 - 2;
+        cpu.exec_instruction(0xF333).unwrap();
+        assert_eq!(cpu.memory[end - 2..], [1, 2, 3]);
+    }
+
+    #[test]
+    fn arithmetic_flag_wins_when_vx_is_vf() {
+        let mut cpu = test_cpu();
+        cpu.v[0xF] = 0xFF;
+        cpu.v[0x1] = 0x02;
+        cpu.exec_instruction(0x8F14).unwrap(); // ADD VF, V1
+        assert_eq!(cpu.v[0xF], 1);
+
+        cpu.v[0xF] = 0x05;
+        cpu.exec_instruction(0x8F15).unwrap(); // SUB VF, V1
+        assert_eq!(cpu.v[0xF], 1);
+
+        cpu.v[0xF] = 0x05;
+        cpu.exec_instruction(0x8F17).unwrap(); // SUBN VF, V1
+        assert_eq!(cpu.v[0xF], 0);
+
+        cpu.v[0xF] = 0x02;
+        cpu.exec_instruction(0x8F06).unwrap(); // SHR VF
+        assert_eq!(cpu.v[0xF], 0);
+
+        cpu.v[0xF] = 0x81;
+        cpu.exec_instruction(0x8F0E).unwrap(); // SHL VF
+        assert_eq!(cpu.v[0xF], 1);
+
+        cpu.v[0x2] = 0x10;
+        cpu.v[0x3] = 0x20;
+        cpu.exec_instruction(0x8235).unwrap(); // SUB V2, V3
+        assert_eq!((cpu.v[0x2], cpu.v[0xF]), (0xF0, 0));
+    }
+
+    #[test]
+    fn step_back_undoes_each_instruction() {
+        let mut cpu = test_cpu();
+        cpu.undo_log = Some(UndoLog::new());
+        cpu.read_sprites_into_memory().unwrap();
+        let program = [
+            0x22, 0x04, // CALL 204
+            0x12, 0x02, // JP 202
+            0x60, 0x05, // V0 = 5
+            0xD0, 0x05, // draw the font's 0 at (5, 5)
+            0xA3, 0x00, // I = 300
+            0xF0, 0x55, // store V0 at 300
+            0x00, 0xE0, // CLS
+            0x00, 0xEE, // RET
+        ];
+        cpu.load_program(&program).unwrap();
+
+        let mut states = vec![];
+        for _ in 0..program.len() / 2 {
+            states.push((cpu.state(), cpu.memory[0x300]));
+            cpu.step().unwrap();
+        }
+        assert_eq!(cpu.pc, 0x202);
+        while let Some((state, mem_300)) = states.pop() {
+            assert!(cpu.step_back());
+            assert_eq!(cpu.state().pc, state.pc);
+            assert_eq!(cpu.state().v, state.v);
+            assert_eq!(cpu.state().i, state.i);
+            assert_eq!(cpu.state().stack, state.stack);
+            assert_eq!(cpu.state().framebuffer, state.framebuffer);
+            assert_eq!(cpu.memory[0x300], mem_300);
+        }
+        assert!(!cpu.step_back());
+    }
+
+    #[test]
+    fn cls_clears_whole_framebuffer() {
+        let mut cpu = test_cpu();
+        for y in 0..ROWS as u16 {
+            for x in 0..COLS as u16 {
+                cpu.display.set_block(x, y);
+            }
+        }
+        assert!(cpu.display.blocks().iter().all(|block| *block == 1));
+
+        cpu.exec_instruction(0x00E0).unwrap();
+        assert!(cpu.display.blocks().iter().all(|block| *block == 0));
+    }
+
+    #[test]
+    fn frames_add_up_to_instructions_per_second() {
+        let mut cpu = test_cpu();
+        for ips in [60, 700, 1000, 1_000_000] {
+            cpu.set_instructions_per_second(ips);
+            let budgets = (0..FPS).map(|_| cpu.frame_budget()).collect::<Vec<_>>();
+            assert_eq!(budgets.iter().sum::<u32>(), ips);
+            let per_frame = ips / FPS;
+            assert!(budgets
+                .iter()
+                .all(|budget| *budget == per_frame || *budget == per_frame + 1));
+        }
+    }
+
+    #[test]
+    fn timers_freeze_while_paused_and_dont_catch_up_on_resume() {
+        let mut cpu = test_cpu();
+        cpu.load_program(&[0xF0, 0x0A, 0x12, 0x02]).unwrap(); // wait for a key, then loop
+        cpu.delay_timer = 10;
+        cpu.sound_timer = 10;
+        cpu.step().unwrap();
+        cpu.run_frame().unwrap();
+        assert_eq!((cpu.delay_timer, cpu.sound_timer), (10, 10));
+
+        // paused by the user, with wall clock time passing that a resume mustn't count
+        cpu.next_key_params = None;
+        cpu.paused = false;
+        cpu.wall_clock_dt = true;
+        cpu.keyboard.pause_toggle_on = true;
+        cpu.delay_timer_checked = Instant::now() - DELAY_TIMER_INTERVAL * 4;
+        cpu.run_frame().unwrap();
+        assert_eq!((cpu.delay_timer, cpu.sound_timer), (10, 10));
+
+        cpu.keyboard.pause_toggle_on = false;
+        cpu.run_frame().unwrap();
+        assert_eq!(cpu.delay_timer, 10); // less than a tick since resuming
+        assert_eq!(cpu.sound_timer, 9);
+    }
+
+    // with no wrap quirk, a sprite over the bottom right corner is clipped at both edges, so
+    // pixels past them can't collide with those at the opposite edges they'd wrap to
+    #[test]
+    fn drw_at_corner_clips_both_edges_and_only_collides_on_screen() {
+        let mut cpu = test_cpu();
+        cpu.memory[0x300..0x302].copy_from_slice(&[0xFF, 0xFF]);
+        cpu.i = 0x300;
+        cpu.v[0x0] = COLS as u8 - 2;
+        cpu.v[0x1] = ROWS as u8 - 1;
+        let block = |x: usize, y: usize| y * COLS + x;
+
+        // lit where the sprite would wrap to, on the left edge and top row
+        cpu.display.set_block(0, ROWS as u16 - 1);
+        cpu.display.set_block(COLS as u16 - 1, 0);
+        cpu.display.set_block(0, 0);
+        cpu.exec_instruction(0xD012).unwrap();
+        assert_eq!(cpu.v[0xF], 0);
+        let blocks = cpu.display.blocks();
+        assert_eq!(blocks.iter().filter(|block| **block == 1).count(), 5);
+        assert_eq!(blocks[block(COLS - 2, ROWS - 1)], 1);
+        assert_eq!(blocks[block(COLS - 1, ROWS - 1)], 1);
+        assert_eq!(blocks[block(0, ROWS - 1)], 1);
+        assert_eq!(blocks[block(COLS - 1, 0)], 1);
+        assert_eq!(blocks[block(0, 0)], 1);
+
+        // drawn again, the visible pixels are erased, and collide
+        cpu.exec_instruction(0xD012).unwrap();
+        assert_eq!(cpu.v[0xF], 1);
+        let blocks = cpu.display.blocks();
+        assert_eq!(blocks.iter().filter(|block| **block == 1).count(), 3);
+        assert_eq!(blocks[block(COLS - 2, ROWS - 1)], 0);
+        assert_eq!(blocks[block(COLS - 1, ROWS - 1)], 0);
+    }
+
+    #[test]
+    fn bcd_boundaries() {
+        assert_eq!(bcd(0), [0, 0, 0]);
+        assert_eq!(bcd(9), [0, 0, 9]);
+        assert_eq!(bcd(10), [0, 1, 0]);
+        assert_eq!(bcd(99), [0, 9, 9]);
+        assert_eq!(bcd(100), [1, 0, 0]);
+        assert_eq!(bcd(255), [2, 5, 5]);
     }
 }
@@ -0,0 +1,73 @@
+use crate::options::parse_hex;
+
+// Commands typed at the debug prompt, to change state while stepping, e.g. "set v3 0x2A"; numbers
+// are hex, with or without 0x
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DebugCommand {
+    SetV(usize, u8),
+    SetI(u16),
+    SetPc(u16),
+    SetDelayTimer(u8),
+    SetSoundTimer(u8),
+    SetMemory(u16, u8),
+}
+
+pub fn parse_command(line: &str) -> Result<DebugCommand, String> {
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    match words.as_slice() {
+        ["set", "i", val] => Ok(DebugCommand::SetI(parse_addr(val)?)),
+        ["set", "pc", val] => Ok(DebugCommand::SetPc(parse_addr(val)?)),
+        ["set", "dt", val] => Ok(DebugCommand::SetDelayTimer(parse_byte(val)?)),
+        ["set", "st", val] => Ok(DebugCommand::SetSoundTimer(parse_byte(val)?)),
+        ["set", "mem", addr, val] => {
+            Ok(DebugCommand::SetMemory(parse_addr(addr)?, parse_byte(val)?))
+        }
+        ["set", reg, val] if reg.starts_with('v') => {
+            let idx = usize::from_str_radix(&reg[1..], 16)
+                .ok()
+                .filter(|idx| *idx < 0x10)
+                .ok_or_else(|| format!("Unknown register {}", reg))?;
+            Ok(DebugCommand::SetV(idx, parse_byte(val)?))
+        }
+        _ => Err(format!(
+            "Unknown command \"{}\", try set v3 2A, set i 300 or set mem 250 FF",
+            line.trim()
+        )),
+    }
+}
+
+fn parse_addr(val: &str) -> Result<u16, String> {
+    parse_hex(val).ok_or_else(|| format!("Invalid address {}", val))
+}
+
+fn parse_byte(val: &str) -> Result<u8, String> {
+    parse_hex(val)
+        .and_then(|val| u8::try_from(val).ok())
+        .ok_or_else(|| format!("Invalid byte {}", val))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_set_commands() {
+        assert_eq!(
+            parse_command("set v3 0x2A"),
+            Ok(DebugCommand::SetV(0x3, 0x2A))
+        );
+        assert_eq!(
+            parse_command("set vf ff"),
+            Ok(DebugCommand::SetV(0xF, 0xFF))
+        );
+        assert_eq!(parse_command("set i 0x300"), Ok(DebugCommand::SetI(0x300)));
+        assert_eq!(
+            parse_command(" set mem 0x250 0xFF "),
+            Ok(DebugCommand::SetMemory(0x250, 0xFF))
+        );
+        assert!(parse_command("set v3 0x100").is_err());
+        assert!(parse_command("set vg 1").is_err());
+        assert!(parse_command("jump 200").is_err());
+    }
+}
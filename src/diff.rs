@@ -0,0 +1,133 @@
+use crate::cpu::Cpu;
+use crate::display::Display;
+use crate::error::{Error, Result};
+use crate::keyboard::Keyboard;
+use crate::options::Options;
+use crate::recording::InputPlayback;
+
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// Runs a ROM headless with recorded input, and compares each frame against a baseline from an
+// earlier build. The first run writes the baseline next to the input recording, as
+// "<inputs>.frames", with one framebuffer per line in hex, 4 blocks per digit.
+pub fn run(rom_path: PathBuf, inputs_path: PathBuf, options: &Options) -> Result<()> {
+    let mut options = options.for_variant().map_err(Error::Options)?;
+    options.seed = options.seed.or(Some(0)); // same random numbers as the baseline
+
+    let mut keyboard = Keyboard::new(options.key_ttl, options.arrow_keys, options.pause_key);
+    // recorded keys are mapped as when played
+    if options.wasd_keys {
+        keyboard.use_wasd_keys();
+    }
+    keyboard.bind_keys(&options.key_bindings);
+    keyboard.play_back(InputPlayback::load(&inputs_path)?);
+    let mut cpu = Cpu::new(Display::new(&options), keyboard, &options);
+
+    let mut frames = vec![];
+    cpu.run_frames(rom_path, options.frames.unwrap_or(3600), |blocks| {
+        frames.push(encode(blocks))
+    })?;
+
+    let baseline_path = baseline_path(&inputs_path);
+    let baseline = match fs::read_to_string(&baseline_path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            fs::write(&baseline_path, frames.join("\n"))?;
+            println!(
+                "Wrote a baseline of {} frames to {}",
+                frames.len(),
+                baseline_path.display()
+            );
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let baseline = baseline.lines().collect::<Vec<_>>();
+
+    let diverged_at = frames
+        .iter()
+        .zip(&baseline)
+        .position(|(frame, expected)| frame != expected);
+    match diverged_at {
+        Some(frame) => {
+            print_frames(baseline[frame], &frames[frame], options.width);
+            return Err(Error::Diverged(format!("Diverged at frame {}", frame)));
+        }
+        None if frames.len() != baseline.len() => {
+            return Err(Error::Diverged(format!(
+                "Ran {} frames, but the baseline has {}",
+                frames.len(),
+                baseline.len()
+            )));
+        }
+        None => println!("No divergence in {} frames", frames.len()),
+    }
+
+    Ok(())
+}
+
+fn baseline_path(inputs_path: &Path) -> PathBuf {
+    let mut path = OsString::from(inputs_path);
+    path.push(".frames");
+    path.into()
+}
+
+// 4 blocks to a hex digit, the last padded with blanks when the size isn't a multiple of 4
+fn encode(blocks: &[u8]) -> String {
+    blocks
+        .chunks(4)
+        .map(|nibble| {
+            let val = nibble.iter().fold(0, |acc, block| acc << 1 | block);
+            let val = val << (4 - nibble.len());
+            char::from_digit(val as u32, 16).unwrap()
+        })
+        .collect()
+}
+
+fn decode(frame: &str) -> Vec<u8> {
+    frame
+        .chars()
+        .flat_map(|digit| {
+            let val = digit.to_digit(16).unwrap_or(0) as u8;
+            (0..4).rev().map(move |bit| val >> bit & 1)
+        })
+        .collect()
+}
+
+// prints the baseline and current frames side by side
+fn print_frames(baseline: &str, current: &str, cols: usize) {
+    let row_str = |blocks: &[u8]| {
+        blocks
+            .iter()
+            .map(|block| if *block == 1 { '#' } else { '.' })
+            .collect::<String>()
+    };
+
+    println!("{:<width$}   current", "baseline", width = cols);
+    let (baseline, current) = (decode(baseline), decode(current));
+    // exact chunks, leaving out the padding
+    for (expected, actual) in baseline.chunks_exact(cols).zip(current.chunks_exact(cols)) {
+        let marker = if expected == actual { ' ' } else { '*' };
+        println!("{} {} {}", row_str(expected), marker, row_str(actual));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_nibble_decodes_in_place() {
+        let blocks = (0..17 * 17)
+            .map(|idx| (idx % 3 == 0) as u8)
+            .collect::<Vec<_>>();
+        let decoded = decode(&encode(&blocks));
+        assert_eq!(decoded.len() % 4, 0);
+        assert_eq!(&decoded[..blocks.len()], blocks.as_slice());
+        assert!(decoded[blocks.len()..].iter().all(|block| *block == 0));
+    }
+}
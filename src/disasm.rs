@@ -0,0 +1,94 @@
+use crate::cpu;
+
+// Disassembles an opcode into the mnemonics of Cowgod's Chip-8 reference, e.g. "DRW V0, V1, 5";
+// bytes are hex, and opcodes the interpreter doesn't implement show as data, e.g. "DW 0xE1A0"
+
+// (syntax, mask, pattern) of each instruction, where an opcode is the instruction if its bits under
+// the mask match the pattern; operands in the syntax are register numbers (Vx, Vy), an address
+// (nnn), a byte (kk) or a nibble (n), and anything else is written as is; shared with the assembler
+pub const INSTRUCTIONS: &[(&str, u16, u16)] = &[
+    ("CLS", 0xFFFF, 0x00E0),
+    ("RET", 0xFFFF, 0x00EE),
+    ("JP nnn", 0xF000, 0x1000),
+    ("CALL nnn", 0xF000, 0x2000),
+    ("SE Vx, kk", 0xF000, 0x3000),
+    ("SNE Vx, kk", 0xF000, 0x4000),
+    ("SE Vx, Vy", 0xF00F, 0x5000),
+    ("LD Vx, kk", 0xF000, 0x6000),
+    ("ADD Vx, kk", 0xF000, 0x7000),
+    ("LD Vx, Vy", 0xF00F, 0x8000),
+    ("OR Vx, Vy", 0xF00F, 0x8001),
+    ("AND Vx, Vy", 0xF00F, 0x8002),
+    ("XOR Vx, Vy", 0xF00F, 0x8003),
+    ("ADD Vx, Vy", 0xF00F, 0x8004),
+    ("SUB Vx, Vy", 0xF00F, 0x8005),
+    ("SHR Vx, Vy", 0xF00F, 0x8006),
+    ("SUBN Vx, Vy", 0xF00F, 0x8007),
+    ("SHL Vx, Vy", 0xF00F, 0x800E),
+    ("SNE Vx, Vy", 0xF00F, 0x9000),
+    ("LD I, nnn", 0xF000, 0xA000),
+    ("JP V0, nnn", 0xF000, 0xB000),
+    ("RND Vx, kk", 0xF000, 0xC000),
+    ("DRW Vx, Vy, n", 0xF000, 0xD000),
+    ("SKP Vx", 0xF0FF, 0xE09E),
+    ("SKNP Vx", 0xF0FF, 0xE0A1),
+    ("LD Vx, DT", 0xF0FF, 0xF007),
+    ("LD Vx, K", 0xF0FF, 0xF00A),
+    ("LD DT, Vx", 0xF0FF, 0xF015),
+    ("LD ST, Vx", 0xF0FF, 0xF018),
+    ("ADD I, Vx", 0xF0FF, 0xF01E),
+    ("LD F, Vx", 0xF0FF, 0xF029),
+    ("LD HF, Vx", 0xF0FF, 0xF030),
+    ("LD B, Vx", 0xF0FF, 0xF033),
+    ("LD [I], Vx", 0xF0FF, 0xF055),
+    ("LD Vx, [I]", 0xF0FF, 0xF065),
+    ("LD R, Vx", 0xF0FF, 0xF075),
+    ("LD Vx, R", 0xF0FF, 0xF085),
+];
+
+pub fn disassemble(opcode: u16) -> String {
+    let syntax = INSTRUCTIONS
+        .iter()
+        .find(|(_, mask, pattern)| opcode & mask == *pattern)
+        .map(|(syntax, _, _)| syntax)
+        .filter(|_| cpu::is_implemented(opcode));
+    let syntax = match syntax {
+        Some(syntax) => syntax,
+        None => return format!("DW 0x{:04X}", opcode),
+    };
+
+    let (mnemonic, operands) = syntax.split_once(' ').unwrap_or((syntax, ""));
+    let operands = operands
+        .split(", ")
+        .filter(|operand| !operand.is_empty())
+        .map(|operand| match operand {
+            "Vx" => format!("V{:X}", (opcode & 0x0F00) >> 8),
+            "Vy" => format!("V{:X}", (opcode & 0x00F0) >> 4),
+            "nnn" => format!("0x{:03X}", opcode & 0x0FFF),
+            "kk" => format!("0x{:02X}", opcode & 0x00FF),
+            "n" => format!("{}", opcode & 0x000F),
+            _ => operand.to_string(),
+        })
+        .collect::<Vec<_>>();
+
+    if operands.is_empty() {
+        mnemonic.to_string()
+    } else {
+        format!("{} {}", mnemonic, operands.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassembles_opcodes() {
+        assert_eq!(disassemble(0x00E0), "CLS");
+        assert_eq!(disassemble(0x6A02), "LD VA, 0x02");
+        assert_eq!(disassemble(0x8126), "SHR V1, V2");
+        assert_eq!(disassemble(0xD015), "DRW V0, V1, 5");
+        assert_eq!(disassemble(0xF355), "LD [I], V3");
+        assert_eq!(disassemble(0xE1A0), "DW 0xE1A0");
+    }
+}
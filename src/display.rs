@@ -1,173 +1,684 @@
+use crate::error::{Error, Result};
+use crate::keyboard::key_name;
+use crate::options::{Charset, Options};
+
 use crossterm::{
-    cursor, execute, queue,
-    style::{Color, Print, SetForegroundColor},
+    cursor, event, execute, queue,
+    style::{Attribute, Color, Print, SetAttribute, SetForegroundColor},
     terminal,
 };
-use std::io::{self, stdout, Write};
+use std::{
+    io::{self, stdout, Write},
+    panic,
+    sync::Once,
+};
 
+// the standard size, unless set with --width and --height
 pub const COLS: usize = 64;
 pub const ROWS: usize = 32;
-const NUM_OF_BLOCKS: usize = COLS * ROWS;
+const FADE_FRAMES: u8 = 3;
+// by remaining fade frames, dimmest first
+const BLOCK_FADE_CHARS: [char; FADE_FRAMES as usize] = ['░', '▒', '▓'];
+const ASCII_FADE_CHARS: [char; FADE_FRAMES as usize] = ['.', ':', '+'];
+// the COSMAC VIP keypad, as laid out in both key maps
+const HEX_KEYS: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+static PANIC_HOOK: Once = Once::new();
 
 pub struct Display {
     stdout: io::Stdout,
-    block_arr: [u8; NUM_OF_BLOCKS],
+    buf: Vec<u8>,      // output for the current frame, reused between frames
+    cell_width: usize, // characters per block, across
+    scale: usize,      // lines per block, down
+    border: String,
+    ruler_border: String, // top border, with column numbers
+    ruler: bool,
+    cols: usize,
+    rows: usize,
+    block_arr: Vec<u8>,
+    fade_arr: Vec<u8>, // frames left for erased blocks to fade out
+    fade: bool,
+    inverted: bool,  // lit pixels drawn blank, on a solid background
+    scanlines: bool, // every other terminal line dimmed
+    color: Color,
+    cls_flash: Option<Color>, // fills the screen for a frame after a clear, to show it happened
+    flash_pending: bool,
+    on_glyph: String,
+    sound_indicator: &'static str,
+    sound_on: bool,
+    muted: bool,
+    recording: bool, // a GIF
+    draw_rate: Option<String>,
+    turbo: bool,
+    quit_prompt: bool, // asks for Esc again, in place of the main menu hint
+    debug_status: Option<String>,
+    key_map_hud: Option<bool>, // shown over the game, if the pause key toggles it
+    pause_key_name: String,
+    key_labels: Vec<String>, // the keys mapped to each hex key, e.g. "2/up"
+    fade_glyphs: Vec<String>,
+    alternate_screen: bool,
+    focus_events: bool, // asks the terminal to report focus changes
+    term_size: (u16, u16),
+    x_offset: u16,
+    y_offset: u16,
 }
 
 impl Display {
-    pub fn new() -> Self {
+    pub fn new(options: &Options) -> Self {
+        let (on_char, fade_chars, sound_indicator) = match options.charset {
+            Charset::Blocks => ('█', BLOCK_FADE_CHARS, "♪ SOUND"),
+            Charset::Ascii(on_char) => (on_char, ASCII_FADE_CHARS, "* SOUND"),
+        };
+        // blocks are 2 characters wide by default, as terminal cells are about twice as tall as
+        // wide
+        let cell_width = options.cell_width * options.scale;
+        let glyph = |ch: char| ch.to_string().repeat(cell_width);
+
         Self {
             stdout: stdout(),
-            block_arr: [0; NUM_OF_BLOCKS],
+            buf: vec![],
+            cell_width,
+            scale: options.scale,
+            border: "=".repeat(options.width * cell_width),
+            ruler_border: ruler_border(options.width, cell_width),
+            ruler: false,
+            cols: options.width,
+            rows: options.height,
+            block_arr: vec![0; options.width * options.height],
+            fade_arr: vec![0; options.width * options.height],
+            fade: options.fade,
+            inverted: false,
+            scanlines: false,
+            color: options.color,
+            cls_flash: options.cls_flash,
+            flash_pending: false,
+            on_glyph: glyph(on_char),
+            sound_indicator,
+            sound_on: false,
+            muted: false,
+            recording: false,
+            draw_rate: None,
+            turbo: false,
+            quit_prompt: false,
+            debug_status: None,
+            key_map_hud: if options.key_map_hud {
+                Some(false)
+            } else {
+                None
+            },
+            pause_key_name: key_name(options.pause_key).to_uppercase(),
+            key_labels: vec![],
+            fade_glyphs: fade_chars.iter().map(|ch| glyph(*ch)).collect(),
+            alternate_screen: !options.no_alt_screen,
+            focus_events: options.focus_pause,
+            term_size: (0, 0),
+            x_offset: 0,
+            y_offset: 0,
         }
     }
 
-    pub fn init(&mut self) -> Result<(), io::Error> {
-        terminal::enable_raw_mode()?;
-        execute!(
-            self.stdout,
-            terminal::EnterAlternateScreen,
-            cursor::Hide,
-            SetForegroundColor(Color::Green),
-        )
-        .unwrap_or_else(|err| {
-            terminal::disable_raw_mode().unwrap();
-            panic!("Failed to initialize display: {:?}", err);
-        });
+    pub fn init(&mut self) -> Result<()> {
+        install_panic_hook();
+
+        // the standard size is drawn however small the terminal, cut off if need be, but a custom
+        // size is checked, as it's easy to pick one too big
+        if (self.cols, self.rows) != (COLS, ROWS) {
+            let (term_cols, term_rows) = terminal::size()
+                .map_err(|err| init_error("Failed to get the terminal size", err))?;
+            let (cols_needed, rows_needed) = (self.frame_width(), self.frame_lines() + 4);
+            if term_cols < cols_needed || term_rows < rows_needed {
+                return Err(init_error(
+                    "Display doesn't fit the terminal",
+                    io::Error::new(
+                        io::ErrorKind::Other,
+                        format!(
+                            "{}x{} pixels need {} columns by {} lines, but it has {} by {}",
+                            self.cols, self.rows, cols_needed, rows_needed, term_cols, term_rows
+                        ),
+                    ),
+                ));
+            }
+        }
+
+        terminal::enable_raw_mode().map_err(|err| init_error("Failed to enable raw mode", err))?;
+
+        if self.alternate_screen && execute!(self.stdout, terminal::EnterAlternateScreen).is_err() {
+            // some consoles don't support the alternate screen, so fall back to drawing over the
+            // main screen, using only cursor positioning
+            self.alternate_screen = false;
+        }
+        if !self.alternate_screen {
+            self.term_size = (0, 0); // forces a full clear on the next render
+        }
+        if self.focus_events {
+            // terminals that don't support it ignore the request, and never report focus changes
+            execute!(self.stdout, event::EnableFocusChange).map_err(|err| {
+                let _ = self.exit();
+                init_error("Failed to enable focus change events", err)
+            })?;
+        }
+
+        execute!(self.stdout, cursor::Hide, SetForegroundColor(self.color)).map_err(|err| {
+            let _ = self.exit();
+            init_error("Failed to initialize display", err)
+        })?;
 
         Ok(())
     }
 
-    pub fn exit(&mut self) -> Result<(), io::Error> {
+    pub fn exit(&mut self) -> Result<()> {
         terminal::disable_raw_mode()?;
-        execute!(self.stdout, terminal::LeaveAlternateScreen, cursor::Show,)?;
+        if self.focus_events {
+            execute!(self.stdout, event::DisableFocusChange)?;
+        }
+        if self.alternate_screen {
+            execute!(self.stdout, terminal::LeaveAlternateScreen, cursor::Show,)?;
+        } else {
+            execute!(
+                self.stdout,
+                terminal::Clear(terminal::ClearType::All),
+                cursor::MoveTo(0, 0),
+                cursor::Show,
+            )?;
+        }
 
         Ok(())
     }
 
+    // a coordinate ruler, to read off DRW positions while debugging
+    pub fn toggle_ruler(&mut self) {
+        self.ruler = !self.ruler;
+        self.term_size = (0, 0); // forces a full clear, as row numbers are drawn outside the frame
+    }
+
     pub fn set_block(&mut self, x: u16, y: u16) -> bool {
-        let block_idx = (x + (y * COLS as u16)) as usize;
+        let block_idx = x as usize + y as usize * self.cols;
         self.block_arr[block_idx] ^= 1; // toggle block
 
-        self.block_arr[block_idx] == 0 // returns true if block erased
+        let erased = self.block_arr[block_idx] == 0;
+        if erased && self.fade {
+            self.fade_arr[block_idx] = FADE_FRAMES;
+        }
+
+        erased // returns true if block erased
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    // 1 for each lit block, row by row
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    pub fn inverted(&self) -> bool {
+        self.inverted
     }
 
+    pub fn blocks(&self) -> &[u8] {
+        &self.block_arr
+    }
+
+    // replaces the framebuffer, e.g. to undo a clear
+    pub fn set_blocks(&mut self, blocks: &[u8]) {
+        self.block_arr.copy_from_slice(blocks);
+    }
+
+    // clears every block, so must cover the whole framebuffer if its size ever changes, e.g. for
+    // SUPER-CHIP's high resolution mode
     pub fn clear(&mut self) {
+        if self.fade {
+            for (block, fade) in self.block_arr.iter().zip(self.fade_arr.iter_mut()) {
+                if *block == 1 {
+                    *fade = FADE_FRAMES;
+                }
+            }
+        }
         self.block_arr.fill(0);
+        self.flash_pending = self.cls_flash.is_some();
     }
 
-    pub fn render(&mut self) -> Result<(), io::Error> {
-        queue!(self.stdout, cursor::MoveTo(0, 0))?;
+    // centers the frame within the terminal, clearing stale output if the terminal was resized
+    fn update_offsets(&mut self) -> Result<()> {
+        let term_size = terminal::size()?;
+        if term_size == self.term_size {
+            return Ok(());
+        }
+
+        let (term_cols, term_rows) = term_size;
+        self.term_size = term_size;
+        self.x_offset = term_cols.saturating_sub(self.frame_width()) / 2;
+        self.y_offset = term_rows.saturating_sub(self.frame_lines() + 4) / 2; // + borders and bar
+
+        queue!(self.buf, terminal::Clear(terminal::ClearType::All))?;
+
+        Ok(())
+    }
+
+    // blocks + left/right borders
+    fn frame_width(&self) -> u16 {
+        (self.cols * self.cell_width) as u16 + 2
+    }
+
+    // lines of blocks, between the top and bottom borders
+    fn frame_lines(&self) -> u16 {
+        (self.rows * self.scale) as u16
+    }
+
+    // writes the queued frame to the terminal in one go
+    fn flush_buf(&mut self) -> Result<()> {
+        self.stdout.write_all(&self.buf)?;
+        self.stdout.flush()?;
+        self.buf.clear();
+
+        Ok(())
+    }
+
+    pub fn render(&mut self) -> Result<()> {
+        self.update_offsets()?;
+        queue!(self.buf, cursor::MoveTo(self.x_offset, self.y_offset))?;
 
-        let top_bottom_border = "=".repeat(COLS * 2);
         queue!(
-            self.stdout,
+            self.buf,
             Print(" "),
-            Print(&top_bottom_border),
-            cursor::MoveDown(1),
-            cursor::MoveToColumn(0),
-            Print("|"),
+            Print(if self.ruler {
+                &self.ruler_border
+            } else {
+                &self.border
+            }),
         )?;
+        if let Some(draw_rate) = &self.draw_rate {
+            queue!(
+                self.buf,
+                cursor::MoveToColumn(self.x_offset + 2),
+                Print(format!(" {} ", draw_rate))
+            )?;
+        }
+        if self.turbo {
+            let turbo_x = self.x_offset + self.frame_width() - 15;
+            queue!(self.buf, cursor::MoveToColumn(turbo_x), Print(" TURBO "))?;
+        }
+        if self.recording {
+            let rec_x = self.x_offset + self.frame_width() - 7;
+            queue!(self.buf, cursor::MoveToColumn(rec_x), Print(" REC "))?;
+        }
 
-        for (idx, block) in self.block_arr.iter().enumerate() {
-            let row = idx / COLS;
+        let flash = self.cls_flash.filter(|_| self.flash_pending);
+        self.flash_pending = false;
+        if let Some(color) = flash {
+            queue!(self.buf, SetForegroundColor(color))?;
+        }
 
-            queue!(self.stdout, Print(if *block == 1 { "██" } else { "  " }))?;
+        let off_glyph = " ".repeat(self.cell_width);
+        let mut row_glyphs = vec![];
+        for row in 0..self.rows {
+            // glyphs are picked once per row, however many lines it's scaled to, as fading blocks
+            // step on as they're picked
+            row_glyphs.clear();
+            for idx in row * self.cols..(row + 1) * self.cols {
+                let glyph = match (self.block_arr[idx], self.fade_arr[idx], self.inverted) {
+                    _ if flash.is_some() => &self.on_glyph,
+                    (1, _, false) | (0, 0, true) => &self.on_glyph,
+                    (1, _, true) | (_, 0, false) => &off_glyph,
+                    (_, fade, inverted) => {
+                        self.fade_arr[idx] -= 1;
+                        // erased blocks fade in from blank when inverted
+                        let step = if inverted {
+                            FADE_FRAMES - fade
+                        } else {
+                            fade - 1
+                        };
+                        &self.fade_glyphs[step as usize]
+                    }
+                };
+                row_glyphs.extend_from_slice(glyph.as_bytes());
+            }
 
-            // end of row
-            if (idx + 1) % COLS == 0 {
+            for line in 0..self.scale {
                 queue!(
-                    self.stdout,
-                    Print("|"),
+                    self.buf,
                     cursor::MoveDown(1),
-                    cursor::MoveToColumn(0),
+                    cursor::MoveToColumn(self.x_offset),
+                    Print("|"),
                 )?;
-
-                // row left border
-                if row != ROWS - 1 {
-                    queue!(self.stdout, Print("|"))?;
+                let dimmed = self.scanlines && (row * self.scale + line) % 2 == 1;
+                if dimmed {
+                    queue!(self.buf, SetAttribute(Attribute::Dim))?;
+                }
+                self.buf.extend_from_slice(&row_glyphs);
+                if dimmed {
+                    queue!(self.buf, SetAttribute(Attribute::NormalIntensity))?;
+                }
+                queue!(self.buf, Print("|"))?;
+                if self.ruler && row % 4 == 0 && line == 0 {
+                    queue!(self.buf, Print(format!(" {}", row)))?;
                 }
             }
         }
 
-        queue!(self.stdout, Print(" "), Print(&top_bottom_border),)?;
+        if flash.is_some() {
+            queue!(self.buf, SetForegroundColor(self.color))?;
+        }
+        queue!(
+            self.buf,
+            cursor::MoveDown(1),
+            cursor::MoveToColumn(self.x_offset),
+            Print(" "),
+            Print(&self.border),
+        )?;
 
+        if self.key_map_hud == Some(true) {
+            self.render_key_map_hud()?;
+        }
         self.render_bottom_bar(false)?;
 
-        self.stdout.flush()?;
+        self.flush_buf()?;
 
         Ok(())
     }
 
-    fn render_bottom_bar(&mut self, paused: bool) -> Result<(), io::Error> {
+    fn render_bottom_bar(&mut self, paused: bool) -> Result<()> {
+        let width = self.frame_width() - 2;
+        let bar_y = self.y_offset + self.frame_lines() + 2;
+        let quit_hint = if self.quit_prompt {
+            "PRESS ESC AGAIN TO QUIT"
+        } else {
+            "MAIN MENU: ESC"
+        };
         queue!(
-            self.stdout,
-            cursor::MoveTo(0, ROWS as u16 + 2),
+            self.buf,
+            cursor::MoveTo(self.x_offset, bar_y),
             terminal::Clear(terminal::ClearType::UntilNewLine),
             Print(format!(
-                " {} KEY MAP: SPACE",
-                if paused {
+                " {} KEY MAP: {}",
+                if self.key_map_hud.is_some() {
+                    "SHOW / HIDE"
+                } else if paused {
                     "RESUME / HIDE"
                 } else {
                     "PAUSE / SHOW"
                 },
+                self.pause_key_name,
             )),
-            cursor::MoveToColumn((COLS as u16 * 2) - 13),
-            Print("MAIN MENU: ESC\n"),
-            cursor::MoveToColumn(1),
-            Print("=".repeat(COLS * 2)),
+            cursor::MoveToColumn(
+                self.x_offset + (width + 1).saturating_sub(quit_hint.len() as u16)
+            ),
+            Print(quit_hint),
+            Print("\n"),
+            cursor::MoveToColumn(self.x_offset + 1),
+            Print(&self.border),
         )?;
 
+        if self.sound_on || self.muted {
+            queue!(
+                self.buf,
+                cursor::MoveTo(self.x_offset + width / 2 - 2, bar_y),
+                Print(if self.muted {
+                    "MUTED"
+                } else {
+                    self.sound_indicator
+                }),
+            )?;
+        }
+        // over the middle of the bottom border, as the bar is full with narrow blocks
+        if let Some(status) = &self.debug_status {
+            let status = format!(" {} ", status);
+            queue!(
+                self.buf,
+                cursor::MoveTo(
+                    self.x_offset + 1 + width.saturating_sub(status.len() as u16) / 2,
+                    bar_y + 1
+                ),
+                Print(status),
+            )?;
+        }
+
         Ok(())
     }
 
-    pub fn render_key_map(&mut self) -> Result<(), io::Error> {
+    // a compact key map, drawn over the middle of the frame, so the game can be seen around it
+    fn render_key_map_hud(&mut self) -> Result<()> {
+        let label_width = self.key_label_width();
+        let mut lines = HEX_KEYS
+            .iter()
+            .enumerate()
+            .map(|(row, hex_keys)| {
+                let hex = hex_keys.map(|key| format!("{:X}", key)).join(" ");
+                let keys = hex_keys
+                    .iter()
+                    .map(|key| format!("{:<1$}", self.key_label(*key), label_width))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let arrow = if row == 0 { "-->" } else { "   " };
+                format!("| {}  {}  {}  |", hex, arrow, keys)
+            })
+            .collect::<Vec<_>>();
+        let edge = format!("+{}+", "-".repeat(lines[0].chars().count() - 2));
+        lines.insert(0, edge.clone());
+        lines.push(edge);
+
+        let width = lines[0].chars().count() as u16;
+        let x = self.x_offset + self.frame_width().saturating_sub(width) / 2;
+        let y = self.y_offset + (self.frame_lines() + 2).saturating_sub(lines.len() as u16) / 2;
+
+        for (row, line) in lines.iter().enumerate() {
+            queue!(self.buf, cursor::MoveTo(x, y + row as u16), Print(line))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_key_labels(&mut self, key_labels: Vec<String>) {
+        self.key_labels = key_labels;
+    }
+
+    fn key_label(&self, hex_key: u8) -> &str {
+        self.key_labels
+            .get(hex_key as usize)
+            .map_or("-", |label| label.as_str())
+    }
+
+    fn key_label_width(&self) -> usize {
+        (0..0x10)
+            .map(|key| self.key_label(key).chars().count())
+            .max()
+            .unwrap_or(1)
+    }
+
+    pub fn set_key_map_hud(&mut self, on: bool) {
+        if let Some(hud_on) = &mut self.key_map_hud {
+            *hud_on = on;
+        }
+    }
+
+    // e.g. "HALTED  FRAME 120  208: D015 DRW V0, V1, 5", shown below the bottom bar while debugging
+    pub fn set_debug_status(&mut self, status: Option<String>) {
+        self.debug_status = status;
+    }
+
+    // shows a visual cue in the bottom bar while the sound timer is active
+    pub fn set_sound_on(&mut self, sound_on: bool) {
+        self.sound_on = sound_on;
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn set_inverted(&mut self, inverted: bool) {
+        self.inverted = inverted;
+    }
+
+    pub fn set_scanlines(&mut self, scanlines: bool) {
+        self.scanlines = scanlines;
+    }
+
+    // shows "REC" over the top border while recording a GIF
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    // shows "TURBO" over the top border, beside any "REC"
+    pub fn set_turbo(&mut self, turbo: bool) {
+        self.turbo = turbo;
+    }
+
+    pub fn set_quit_prompt(&mut self, quit_prompt: bool) {
+        self.quit_prompt = quit_prompt;
+    }
+
+    // e.g. "DRW 120/s  CLS 60/s", shown over the top border
+    pub fn set_draw_rate(&mut self, draw_rate: Option<String>) {
+        self.draw_rate = draw_rate;
+    }
+
+    // keys held down, by hex key, are highlighted in both grids, to show input is getting through
+    pub fn render_key_map(&mut self, pressed: &[bool]) -> Result<()> {
+        self.update_offsets()?;
+
         let margin = 16;
         let row_len = 16;
-        let y_start = 12;
-        let grid_1_x = 41;
+        // the keys' grid widens for longer names, e.g. "2/up"
+        let label_width = self.key_label_width() as u16;
+        let cell_2_width = (label_width + 2).max(5);
+        let row_2_len = cell_2_width * 3 + label_width;
+        let y_start = (self.y_offset + self.frame_lines() / 2).saturating_sub(4);
+        let grid_1_x = (self.x_offset + self.frame_width() / 2)
+            .saturating_sub((row_len + margin + row_2_len) / 2);
         let grid_2_x = grid_1_x + row_len + margin;
 
-        queue!(self.stdout, terminal::Clear(terminal::ClearType::All))?;
-
         queue!(
-            self.stdout,
+            self.buf,
+            terminal::Clear(terminal::ClearType::All),
             cursor::MoveTo(grid_1_x, y_start),
-            Print("HEX\n\n"),
-            cursor::MoveToColumn(grid_1_x),
-            Print("1    2    3    C\n\n"),
-            cursor::MoveToColumn(grid_1_x),
-            Print("4    5    6    D\n\n"),
-            cursor::MoveToColumn(grid_1_x),
-            Print("7    8    9    E\n\n"),
-            cursor::MoveToColumn(grid_1_x),
-            Print("A    0    B    F"),
-        )?;
-
-        queue!(
-            self.stdout,
+            Print("HEX"),
+            cursor::MoveTo(grid_2_x, y_start),
+            Print("KEYS"),
             cursor::MoveTo(grid_1_x + row_len + (margin / 2) - 2, y_start + 5,),
             Print("--->"),
         )?;
 
+        for (row, hex_keys) in HEX_KEYS.iter().enumerate() {
+            let y = y_start + 2 + row as u16 * 2;
+            for (col, hex_key) in hex_keys.iter().enumerate() {
+                let label = self.key_label(*hex_key).to_string();
+                let is_pressed = pressed.get(*hex_key as usize) == Some(&true);
+                if is_pressed {
+                    queue!(self.buf, SetAttribute(Attribute::Reverse))?;
+                }
+                queue!(
+                    self.buf,
+                    cursor::MoveTo(grid_1_x + col as u16 * 5, y),
+                    Print(format!("{:X}", hex_key)),
+                    cursor::MoveTo(grid_2_x + col as u16 * cell_2_width, y),
+                    Print(label),
+                )?;
+                if is_pressed {
+                    queue!(self.buf, SetAttribute(Attribute::NoReverse))?;
+                }
+            }
+        }
+
+        self.render_bottom_bar(true)?;
+
+        self.flush_buf()?;
+
+        Ok(())
+    }
+
+    // lists the emulator's own hotkeys, in the same style as the key map
+    pub fn render_help(&mut self, hotkeys: &[(String, &str)]) -> Result<()> {
+        let rows = hotkeys
+            .iter()
+            .map(|(key, action)| (key.to_uppercase(), action.to_string()))
+            .collect::<Vec<_>>();
+        self.render_table("HOTKEYS", 12, &rows)
+    }
+
+    // e.g. ("DISPLAY WAIT", "off"), for each quirk
+    pub fn render_quirks(&mut self, quirks: &[(&str, String)]) -> Result<()> {
+        let rows = quirks
+            .iter()
+            .map(|(quirk, setting)| (quirk.to_uppercase(), setting.clone()))
+            .collect::<Vec<_>>();
+        self.render_table("QUIRKS", 20, &rows)
+    }
+
+    // a heading, then rows of two columns, centered over a cleared screen
+    fn render_table(
+        &mut self,
+        heading: &str,
+        key_col_len: u16,
+        rows: &[(String, String)],
+    ) -> Result<()> {
+        self.update_offsets()?;
+
+        let row_len = key_col_len + 24;
+        let x_start = (self.x_offset + self.frame_width() / 2).saturating_sub(row_len / 2);
+        let y_start = (self.y_offset + self.frame_lines() / 2).saturating_sub(rows.len() as u16);
+
         queue!(
-            self.stdout,
-            cursor::MoveTo(grid_2_x, y_start),
-            Print("QWERTY\n\n"),
-            cursor::MoveToColumn(grid_2_x),
-            Print("1    2    3    4\n\n"),
-            cursor::MoveToColumn(grid_2_x),
-            Print("q    w    e    r\n\n"),
-            cursor::MoveToColumn(grid_2_x),
-            Print("a    s    d    f\n\n"),
-            cursor::MoveToColumn(grid_2_x),
-            Print("z    x    c    v"),
+            self.buf,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(x_start, y_start),
+            Print(format!("{}\n", heading)),
         )?;
+        for (key, val) in rows {
+            queue!(
+                self.buf,
+                cursor::MoveDown(1),
+                cursor::MoveToColumn(x_start),
+                Print(format!(
+                    "{:<width$}{}\n",
+                    key,
+                    val,
+                    width = key_col_len as usize
+                )),
+            )?;
+        }
 
         self.render_bottom_bar(true)?;
 
-        self.stdout.flush()?;
+        self.flush_buf()?;
 
         Ok(())
     }
 }
+
+// border with the number of every 8th column above it, e.g. "0===============8====..."
+fn ruler_border(cols: usize, cell_width: usize) -> String {
+    let mut border = vec!['='; cols * cell_width];
+    for col in (0..cols).step_by(8) {
+        for (offset, digit) in col.to_string().chars().enumerate() {
+            border[col * cell_width + offset] = digit;
+        }
+    }
+    border.into_iter().collect()
+}
+
+fn init_error(context: &'static str, err: io::Error) -> Error {
+    Error::DisplayInit(context, err)
+}
+
+// restores the terminal before the panic message is printed, so a crash doesn't leave it in raw
+// mode on the alternate screen
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let _ = terminal::disable_raw_mode();
+            let _ = execute!(stdout(), terminal::LeaveAlternateScreen, cursor::Show);
+            default_hook(info);
+        }));
+    });
+}
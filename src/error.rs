@@ -0,0 +1,103 @@
+use std::{error, fmt, io};
+
+// Errors that end emulation, or the program, each with its own process exit code
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Options(String), // invalid options, e.g. in a ROM's options file
+    Zip(zip::result::ZipError),
+    DisplayInit(&'static str, io::Error), // what failed, e.g. "Failed to enable raw mode"
+    RomTooLarge { len: usize, load_addr: u16 },
+    Asm { line: usize, message: String }, // invalid assembly source
+    State(String),                        // why a save state can't be loaded
+    Diverged(String),                     // where diff found frames unlike the baseline's
+    UnknownOpcode(String),                // with where it ran, e.g. "0x5001 at 0x2A4 (I = 0x300)"
+    StackOverflow(String),
+    StackUnderflow(String),
+    MemoryOverflow(String),
+    PcOutOfBounds(String),
+    SelfTestFailed { failed: u8, run: u8 }, // counts of the built-in self-test's checks
+}
+
+impl Error {
+    // 1 is left for any other error, e.g. invalid options or a missing file
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Io(_) | Error::Options(_) | Error::Zip(_) | Error::DisplayInit(..) => 1,
+            Error::Asm { .. } | Error::State(_) | Error::Diverged(_) => 1,
+            Error::RomTooLarge { .. } => 2,
+            Error::UnknownOpcode(_) => 3,
+            Error::StackUnderflow(_) => 4,
+            Error::MemoryOverflow(_) => 5,
+            Error::StackOverflow(_) => 6,
+            Error::PcOutOfBounds(_) => 7,
+            Error::SelfTestFailed { .. } => 8,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Options(message) => write!(f, "{}", message),
+            Error::Zip(err) => write!(f, "{}", err),
+            Error::DisplayInit(context, err) => write!(f, "{}: {}", context, err),
+            Error::RomTooLarge { len, load_addr } => write!(
+                f,
+                "ROM of {} bytes doesn't fit in memory when loaded at 0x{:X}",
+                len, load_addr
+            ),
+            Error::Asm { line, message } => {
+                write!(f, "Assembly error on line {}: {}", line, message)
+            }
+            Error::State(reason) => write!(f, "Invalid save state: {}", reason),
+            Error::Diverged(message) => write!(f, "{}", message),
+            Error::UnknownOpcode(context) => write!(f, "Unknown opcode {}", context),
+            Error::StackOverflow(context) => {
+                write!(f, "Attempt to push onto full stack, by {}", context)
+            }
+            Error::StackUnderflow(context) => {
+                write!(f, "Attempt to pop from empty stack, by {}", context)
+            }
+            Error::MemoryOverflow(context) => {
+                write!(f, "Attempt to write past the end of memory, by {}", context)
+            }
+            Error::PcOutOfBounds(context) => {
+                write!(
+                    f,
+                    "Program counter ran past the end of memory, after {}",
+                    context
+                )
+            }
+            Error::SelfTestFailed { failed, run } => {
+                write!(f, "Self-test failed {} of {} tests", failed, run)
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) | Error::DisplayInit(_, err) => Some(err),
+            Error::Zip(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<zip::result::ZipError> for Error {
+    fn from(err: zip::result::ZipError) -> Self {
+        Error::Zip(err)
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Read},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+// Gamepad input from the Linux joystick API (/dev/input/js*), which, unlike terminals, reports
+// releases, so held buttons stay down. gilrs would cover more platforms, but needs a newer
+// toolchain than this crate builds with.
+
+const EVENT_BUTTON: u8 = 0x01;
+const EVENT_AXIS: u8 = 0x02;
+const EVENT_INIT: u8 = 0x80; // initial state, sent on open
+const AXIS_THRESHOLD: i16 = 16384; // half way
+const READER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+// from Linux's fcntl.h, as libc isn't a dependency
+#[cfg(unix)]
+const O_NONBLOCK: i32 = 0o4000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadInput {
+    Button(u8),
+    AxisNeg(u8), // e.g. left/up
+    AxisPos(u8), // e.g. right/down
+}
+
+pub struct Gamepad {
+    event_rx: Receiver<(u8, u8, i16)>, // (type, number, value)
+    map: HashMap<GamepadInput, u8>,
+    held_inputs: HashSet<GamepadInput>,
+    reader: Option<(JoinHandle<()>, Arc<AtomicBool>)>, // the thread, and a flag to stop it
+}
+
+impl Gamepad {
+    pub fn open(path: &Path, map: HashMap<GamepadInput, u8>) -> Result<Self, io::Error> {
+        let mut file = open_nonblocking(path)?;
+        let (tx, rx) = channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+
+        // reads don't block, so the thread notices when it's stopped, rather than waiting for (and
+        // swallowing) the next event, which belongs to the next ROM's gamepad
+        let handle = thread::spawn(move || {
+            let mut event = [0; 8]; // time: u32, value: i16, type: u8, number: u8
+            while !thread_stop.load(Ordering::Relaxed) {
+                match file.read(&mut event) {
+                    Ok(8) => (),
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        thread::sleep(READER_POLL_INTERVAL);
+                        continue;
+                    }
+                    _ => break, // unplugged
+                }
+                let value = i16::from_ne_bytes([event[4], event[5]]);
+                if tx.send((event[6] & !EVENT_INIT, event[7], value)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            event_rx: rx,
+            map,
+            held_inputs: HashSet::new(),
+            reader: Some((handle, stop)),
+        })
+    }
+
+    pub fn close(&mut self) {
+        if let Some((handle, stop)) = self.reader.take() {
+            stop.store(true, Ordering::Relaxed);
+            handle.join().expect("Gamepad reader thread panicked");
+        }
+    }
+
+    // applies events since the last poll, and returns the CHIP-8 keys newly pressed
+    pub fn poll(&mut self) -> Vec<u8> {
+        let mut pressed = vec![];
+        while let Ok((event_type, number, value)) = self.event_rx.try_recv() {
+            let (input, released) = match event_type {
+                EVENT_BUTTON => (GamepadInput::Button(number), value == 0),
+                EVENT_AXIS => {
+                    self.held_inputs.remove(&GamepadInput::AxisNeg(number));
+                    self.held_inputs.remove(&GamepadInput::AxisPos(number));
+                    match value {
+                        _ if value <= -AXIS_THRESHOLD => (GamepadInput::AxisNeg(number), false),
+                        _ if value >= AXIS_THRESHOLD => (GamepadInput::AxisPos(number), false),
+                        _ => continue, // centered
+                    }
+                }
+                _ => continue,
+            };
+
+            if released {
+                self.held_inputs.remove(&input);
+            } else if self.held_inputs.insert(input) {
+                pressed.extend(self.map.get(&input));
+            }
+        }
+
+        pressed
+    }
+
+    pub fn is_key_held(&self, key: u8) -> bool {
+        self.held_inputs
+            .iter()
+            .any(|input| self.map.get(input) == Some(&key))
+    }
+}
+
+#[cfg(unix)]
+fn open_nonblocking(path: &Path) -> Result<fs::File, io::Error> {
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(O_NONBLOCK)
+        .open(path)
+}
+
+// there are no joystick devices to read elsewhere
+#[cfg(not(unix))]
+fn open_nonblocking(path: &Path) -> Result<fs::File, io::Error> {
+    fs::File::open(path)
+}
+
+// d-pad and left stick as 2/4/6/8, and the first face buttons as common action keys
+pub fn default_map() -> HashMap<GamepadInput, u8> {
+    let mut map = HashMap::new();
+    for axes in [(0, 1), (6, 7)] {
+        map.extend([
+            (GamepadInput::AxisNeg(axes.0), 0x4),
+            (GamepadInput::AxisPos(axes.0), 0x6),
+            (GamepadInput::AxisNeg(axes.1), 0x2),
+            (GamepadInput::AxisPos(axes.1), 0x8),
+        ]);
+    }
+    map.extend([
+        (GamepadInput::Button(0), 0x5),
+        (GamepadInput::Button(1), 0xA),
+        (GamepadInput::Button(2), 0xB),
+        (GamepadInput::Button(3), 0xF),
+    ]);
+    map
+}
+
+// parses mappings like "button0=5,axis1-=2", with hex CHIP-8 keys
+pub fn parse_map(val: &str) -> Option<HashMap<GamepadInput, u8>> {
+    val.split(',')
+        .map(|entry| {
+            let (input, key) = entry.trim().split_once('=')?;
+            let input = if let Some(num) = input.strip_prefix("button") {
+                GamepadInput::Button(num.parse().ok()?)
+            } else if let Some(num) = input.strip_prefix("axis").and_then(|a| a.strip_suffix('-')) {
+                GamepadInput::AxisNeg(num.parse().ok()?)
+            } else if let Some(num) = input.strip_prefix("axis").and_then(|a| a.strip_suffix('+')) {
+                GamepadInput::AxisPos(num.parse().ok()?)
+            } else {
+                return None;
+            };
+            let key = u8::from_str_radix(key, 16).ok().filter(|key| *key <= 0xF)?;
+            Some((input, key))
+        })
+        .collect()
+}
@@ -0,0 +1,100 @@
+// Detects busy-wait loops, e.g. a ROM reading DT until it reaches 0, or skipping a jump back
+// until a key is pressed, so the rest of the frame can be skipped instead of spinning. A loop is
+// one ending in a short jump back, and it's idle once a pass through it changes nothing: the
+// registers are the same as the last time the jump ran, and nothing in between wrote memory, drew,
+// or set a timer. Only the timers and keys can then end the loop, and they only change between
+// frames, or are read again at the start of the next one.
+
+const MAX_LOOP_BYTES: u16 = 0x10; // how far back the jump may go, i.e. 8 instructions
+
+#[derive(PartialEq)]
+struct Snapshot {
+    jump_addr: u16,
+    v: [u8; 0x10],
+    i: u16,
+    stack_len: usize,
+}
+
+pub struct IdleDetector {
+    last_jump: Option<Snapshot>,
+    changed: bool, // whether an instruction since the last jump had side effects
+    idle: bool,
+}
+
+impl IdleDetector {
+    pub fn new() -> Self {
+        Self {
+            last_jump: None,
+            changed: false,
+            idle: false,
+        }
+    }
+
+    // called after each instruction, with the state it left
+    pub fn observe(&mut self, addr: u16, opcode: u16, v: &[u8; 0x10], i: u16, stack_len: usize) {
+        if has_side_effects(opcode) {
+            self.changed = true;
+        }
+
+        let target = opcode & 0xFFF;
+        if opcode & 0xF000 != 0x1000 || target > addr || addr - target > MAX_LOOP_BYTES {
+            return;
+        }
+        let snapshot = Snapshot {
+            jump_addr: addr,
+            v: *v,
+            i,
+            stack_len,
+        };
+        if !self.changed && self.last_jump.as_ref() == Some(&snapshot) {
+            self.idle = true;
+        }
+        self.last_jump = Some(snapshot);
+        self.changed = false;
+    }
+
+    // whether the ROM was found idle, since last asked
+    pub fn take_idle(&mut self) -> bool {
+        std::mem::take(&mut self.idle)
+    }
+}
+
+// anything that changes state other than the registers and I, or uses the RNG
+fn has_side_effects(opcode: u16) -> bool {
+    match opcode & 0xF000 {
+        0x0000 => opcode == 0x00E0,
+        0xC000 | 0xD000 => true,
+        0xF000 => matches!(opcode & 0xFF, 0x0A | 0x15 | 0x18 | 0x33 | 0x55 | 0x75),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_loop_that_changes_nothing() {
+        let mut idle = IdleDetector::new();
+        let mut v = [0; 0x10];
+
+        // 0x300: LD V0, DT; SE V0, 0; JP 0x300, while DT counts down
+        for dt in [2, 1] {
+            v[0x0] = dt;
+            idle.observe(0x300, 0xF007, &v, 0, 0);
+            idle.observe(0x302, 0x3000, &v, 0, 0);
+            idle.observe(0x304, 0x1300, &v, 0, 0);
+            assert!(!idle.take_idle());
+        }
+        idle.observe(0x300, 0xF007, &v, 0, 0);
+        idle.observe(0x302, 0x3000, &v, 0, 0);
+        idle.observe(0x304, 0x1300, &v, 0, 0);
+        assert!(idle.take_idle());
+        assert!(!idle.take_idle());
+
+        // a draw in the loop keeps it from being idle
+        idle.observe(0x302, 0xD015, &v, 0, 0);
+        idle.observe(0x304, 0x1300, &v, 0, 0);
+        assert!(!idle.take_idle());
+    }
+}
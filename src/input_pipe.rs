@@ -0,0 +1,122 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+// Key input from a named pipe, one command per line, e.g. "press 5" then "release 5", with hex
+// CHIP-8 keys, for macro tools and assistive devices. Like gamepads, and unlike terminals, the pipe
+// reports releases, so keys stay held until released. The pipe is opened once for the session,
+// and each ROM's keyboard gets a clone, as the reading thread can't be stopped while it waits for a
+// writer, and would take the next one's first line if it were left behind.
+
+#[derive(Clone)]
+pub struct InputPipe {
+    event_rx: Arc<Mutex<Receiver<(u8, bool)>>>, // (key, pressed)
+    held_keys: HashSet<u8>,
+}
+
+impl InputPipe {
+    pub fn open(path: &Path) -> Result<Self, io::Error> {
+        // fail early if it doesn't exist, as opening blocks for a writer, or if it's a regular
+        // file, which would never block, and be replayed over and over
+        if !is_fifo(path)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} isn't a named pipe", path.display()),
+            ));
+        }
+        let path = PathBuf::from(path);
+        let (tx, rx) = channel();
+
+        // opening a pipe blocks until a writer opens it, and reads until the writer closes it, when
+        // it's reopened for the next one
+        thread::spawn(move || {
+            while let Ok(file) = fs::File::open(&path) {
+                for line in io::BufReader::new(file).lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break,
+                    };
+                    if let Some(event) = parse_command(&line) {
+                        if tx.send(event).is_err() {
+                            return; // keyboard dropped
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            event_rx: Arc::new(Mutex::new(rx)),
+            held_keys: HashSet::new(),
+        })
+    }
+
+    // applies commands since the last poll, and returns the keys newly pressed
+    pub fn poll(&mut self) -> Vec<u8> {
+        let mut pressed = vec![];
+        let event_rx = match self.event_rx.lock() {
+            Ok(event_rx) => event_rx,
+            Err(_) => return pressed,
+        };
+        while let Ok((key, is_press)) = event_rx.try_recv() {
+            if !is_press {
+                self.held_keys.remove(&key);
+            } else if self.held_keys.insert(key) {
+                pressed.push(key);
+            }
+        }
+
+        pressed
+    }
+
+    pub fn is_key_held(&self, key: u8) -> bool {
+        self.held_keys.contains(&key)
+    }
+}
+
+#[cfg(unix)]
+fn is_fifo(path: &Path) -> Result<bool, io::Error> {
+    use std::os::unix::fs::FileTypeExt;
+    Ok(fs::metadata(path)?.file_type().is_fifo())
+}
+
+// only Unix has named pipes that can be opened as files
+#[cfg(not(unix))]
+fn is_fifo(path: &Path) -> Result<bool, io::Error> {
+    fs::metadata(path)?;
+    Ok(false)
+}
+
+// parses "press <key>" or "release <key>", ignoring anything else
+fn parse_command(line: &str) -> Option<(u8, bool)> {
+    let (command, key) = line.trim().split_once(' ')?;
+    let key = u8::from_str_radix(key.trim(), 16)
+        .ok()
+        .filter(|key| *key < 0x10)?;
+    match command {
+        "press" => Some((key, true)),
+        "release" => Some((key, false)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse_command("press 5"), Some((0x5, true)));
+        assert_eq!(parse_command("release a\n"), Some((0xA, false)));
+        assert_eq!(parse_command("press 10"), None);
+        assert_eq!(parse_command("hold 5"), None);
+    }
+}
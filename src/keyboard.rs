@@ -1,94 +1,478 @@
+use crate::gamepad::Gamepad;
+use crate::input_pipe::InputPipe;
+use crate::recording::{InputPlayback, InputRecording};
+
 use crossterm::event;
 use std::{
     collections::HashMap,
-    sync::mpsc::{channel, Receiver, Sender, TryRecvError},
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, Sender, TryRecvError},
+        Arc,
+    },
     thread,
     time::{Duration, Instant},
 };
 
-const KEY_PRESS_TTL: Duration = Duration::from_millis(100);
+const LISTENER_POLL_INTERVAL: Duration = Duration::from_millis(50);
+const FRAME_INTERVAL: Duration = Duration::from_millis(1000 / 60);
+// how long after a first Esc a second one quits, when quitting needs confirmation
+const QUIT_CONFIRM_WINDOW: Duration = Duration::from_secs(1);
+// raw mode disables SIGINT, so Ctrl-C is passed along as the character the terminal would send
+pub const CTRL_C: event::KeyCode = event::KeyCode::Char('\u{3}');
 
 pub struct Keyboard {
-    key_map: HashMap<char, u8>,
+    key_map: HashMap<event::KeyCode, u8>, // several keys may map to the same CHIP-8 key
     key_press_tx: Sender<(event::KeyCode, Instant)>,
     key_press_rx: Receiver<(event::KeyCode, Instant)>,
+    focus_tx: Sender<bool>, // focus changes, true when gained, if the terminal reports them
+    focus_rx: Receiver<bool>,
+    focus_paused: bool, // paused as focus was lost, rather than by the user
+    listener: Option<(thread::JoinHandle<()>, Arc<AtomicBool>)>, // (thread, stop flag)
     pressed_keys: HashMap<u8, Instant>,
+    key_press_ttl: Duration, // terminals don't report key releases, so presses expire instead
+    pause_key: event::KeyCode, // toggles pause, and the key map overlay
+    key_map_hud: bool,       // the pause key only toggles the key map, over the running game
+    starts_paused: bool,
+    confirm_quit: bool,
+    quit_requested_at: Option<Instant>, // when Esc was first pressed, if quitting needs confirmation
+    frame: u64,
+    frame_clock: Option<Instant>, // start of frame 0, when time is measured in frames
+    debug_keys: bool,
+    any_key_quits: bool,
+    debug_presses: Vec<u8>,       // function key numbers, e.g. 2 for F2
+    debug_prompt: Option<String>, // a command being typed, which takes all keys but Ctrl-C
+    debug_commands: Vec<String>,
+    gamepad: Option<Gamepad>,
+    input_pipe: Option<InputPipe>,
+    recording: Option<InputRecording>,
+    playback: Option<InputPlayback>,
     pub esc_pressed: bool,
+    pub ctrl_c_pressed: bool,
     pub pause_toggle_on: bool,
+    pub help_on: bool, // the hotkeys overlay, shown while paused instead of the key map
+    pub quirks_on: bool, // likewise, the overlay of quirk settings
+    pub hud_toggle_on: bool,
+    pub gif_toggle_on: bool,
+    pub turbo_toggle_on: bool,
+    pub muted: bool, // set from --mute for each ROM, so M only lasts until the ROM exits
+    pub inverted: bool,
+    pub scanlines: bool,
 }
 
 impl Keyboard {
-    pub fn new() -> Self {
+    pub fn new(key_press_ttl: Duration, arrow_keys: bool, pause_key: event::KeyCode) -> Self {
         let (tx, rx) = channel::<(event::KeyCode, Instant)>();
+        let (focus_tx, focus_rx) = channel();
 
         Self {
-            key_map: HashMap::from([
-                ('1', 0x1),
-                ('2', 0x2),
-                ('3', 0x3),
-                ('4', 0xC),
-                ('q', 0x4),
-                ('w', 0x5),
-                ('e', 0x6),
-                ('r', 0xD),
-                ('a', 0x7),
-                ('s', 0x8),
-                ('d', 0x9),
-                ('f', 0xE),
-                ('z', 0xA),
-                ('x', 0x0),
-                ('c', 0xB),
-                ('v', 0xF),
-            ]),
+            key_map: default_key_map(arrow_keys),
             key_press_tx: tx,
             key_press_rx: rx,
+            focus_tx,
+            focus_rx,
+            focus_paused: false,
+            listener: None,
             pressed_keys: HashMap::new(),
+            key_press_ttl,
+            pause_key,
+            key_map_hud: false,
+            starts_paused: false,
+            confirm_quit: false,
+            quit_requested_at: None,
+            frame: 0,
+            frame_clock: None,
+            debug_keys: false,
+            any_key_quits: false,
+            debug_presses: vec![],
+            debug_prompt: None,
+            debug_commands: vec![],
+            gamepad: None,
+            input_pipe: None,
+            recording: None,
+            playback: None,
             esc_pressed: false,
+            ctrl_c_pressed: false,
             pause_toggle_on: false,
+            help_on: false,
+            quirks_on: false,
+            hud_toggle_on: false,
+            gif_toggle_on: false,
+            turbo_toggle_on: false,
+            muted: false,
+            inverted: false,
+            scanlines: false,
         }
     }
 
     pub fn init(&mut self) {
+        self.flush();
+        self.pause_toggle_on = self.starts_paused;
         self.listen();
     }
 
+    // discards input left over from before emulation started, e.g. from a previous ROM
+    pub fn flush(&mut self) {
+        if self.listener.is_none() {
+            while event::poll(Duration::ZERO).unwrap_or(false) {
+                if event::read().is_err() {
+                    break;
+                }
+            }
+        }
+        while self.key_press_rx.try_recv().is_ok() {}
+        while self.focus_rx.try_recv().is_ok() {}
+        self.focus_paused = false;
+
+        self.pressed_keys.clear();
+        self.debug_presses.clear();
+        self.debug_prompt = None;
+        self.debug_commands.clear();
+        self.esc_pressed = false;
+        self.ctrl_c_pressed = false;
+        self.quit_requested_at = None;
+        self.pause_toggle_on = false;
+        self.help_on = false;
+        self.quirks_on = false;
+        self.hud_toggle_on = false;
+        self.gif_toggle_on = false;
+        self.turbo_toggle_on = false;
+    }
+
+    // names of the keys mapped to each hex key, e.g. "2/up" for 2 with --arrows, or "-" for none,
+    // for the key maps
+    pub fn key_labels(&self) -> Vec<String> {
+        (0..0x10)
+            .map(|hex_key| {
+                let mut names = self
+                    .key_map
+                    .iter()
+                    .filter(|(_, val)| **val == hex_key)
+                    .map(|(key, _)| key_name(*key))
+                    .collect::<Vec<_>>();
+                names.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+                match names.is_empty() {
+                    true => "-".to_string(),
+                    false => names.join("/"),
+                }
+            })
+            .collect()
+    }
+
+    // W/A/S/D as a directional pad, swapping the hex keys they had with those of 2, Q and E, the
+    // keys standing where 2/4/6 are on the COSMAC VIP keypad (S is already 8)
+    pub fn use_wasd_keys(&mut self) {
+        for (wasd_key, other_key) in [('w', '2'), ('a', 'q'), ('d', 'e')] {
+            let (wasd_key, other_key) = (
+                event::KeyCode::Char(wasd_key),
+                event::KeyCode::Char(other_key),
+            );
+            if let (Some(wasd_val), Some(other_val)) = (
+                self.key_map.get(&wasd_key).copied(),
+                self.key_map.get(&other_key).copied(),
+            ) {
+                self.key_map.insert(wasd_key, other_val);
+                self.key_map.insert(other_key, wasd_val);
+            }
+        }
+    }
+
+    // maps more keys to CHIP-8 keys, alongside or replacing the defaults, e.g. both w and up to 2
+    pub fn bind_keys(&mut self, bindings: &[(event::KeyCode, u8)]) {
+        self.key_map.extend(bindings.iter().copied());
+    }
+
+    // makes every key act like Esc, e.g. to leave a demo
+    pub fn quit_on_any_key(&mut self) {
+        self.any_key_quits = true;
+    }
+
+    pub fn use_key_map_hud(&mut self) {
+        self.key_map_hud = true;
+    }
+
+    // waits for the pause key before the ROM runs
+    pub fn start_paused(&mut self) {
+        self.starts_paused = true;
+    }
+
+    // makes Esc only quit when pressed twice, within QUIT_CONFIRM_WINDOW
+    pub fn confirm_quit(&mut self) {
+        self.confirm_quit = true;
+    }
+
+    // whether a first Esc is waiting to be confirmed
+    pub fn quit_prompt_on(&self) -> bool {
+        self.quit_requested_at
+            .map_or(false, |at| at.elapsed() < QUIT_CONFIRM_WINDOW)
+    }
+
+    // an unconfirmed Esc is noted, for a second one to confirm
+    fn esc_confirmed(&mut self, timestamp: Instant) -> bool {
+        if !self.confirm_quit {
+            return true;
+        }
+        match self.quit_requested_at.take() {
+            Some(at) if timestamp.saturating_duration_since(at) < QUIT_CONFIRM_WINDOW => true,
+            _ => {
+                self.quit_requested_at = Some(timestamp);
+                false
+            }
+        }
+    }
+
+    // function keys are reserved for debugging tools, e.g. F6 steps a frame
+    pub fn enable_debug_keys(&mut self) {
+        self.debug_keys = true;
+    }
+
+    // debug keys pressed since the last call, as function key numbers
+    pub fn take_debug_presses(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.debug_presses)
+    }
+
+    // commands entered at the debug prompt since the last call
+    pub fn take_debug_commands(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.debug_commands)
+    }
+
+    // the command being typed, while the debug prompt is open
+    pub fn debug_prompt(&self) -> Option<&str> {
+        self.debug_prompt.as_deref()
+    }
+
+    fn handle_prompt_key(&mut self, key: event::KeyCode) {
+        let prompt = match &mut self.debug_prompt {
+            Some(prompt) => prompt,
+            None => return,
+        };
+        match key {
+            event::KeyCode::Enter => {
+                if let Some(command) = self.debug_prompt.take() {
+                    self.debug_commands.push(command);
+                }
+            }
+            event::KeyCode::Esc => self.debug_prompt = None,
+            event::KeyCode::Backspace => {
+                prompt.pop();
+            }
+            event::KeyCode::Char(ch) => prompt.push(ch),
+            _ => (),
+        }
+    }
+
+    // polled alongside the keyboard; gamepad input isn't recorded
+    pub fn use_gamepad(&mut self, gamepad: Gamepad) {
+        self.gamepad = Some(gamepad);
+    }
+
+    // polled alongside the keyboard, like a gamepad, and likewise not recorded
+    pub fn use_input_pipe(&mut self, input_pipe: InputPipe) {
+        self.input_pipe = Some(input_pipe);
+    }
+
+    pub fn record_to(&mut self, recording: InputRecording) {
+        self.recording = Some(recording);
+    }
+
+    pub fn play_back(&mut self, playback: InputPlayback) {
+        self.playback = Some(playback);
+    }
+
+    pub fn save_recording(&self) -> Result<(), io::Error> {
+        match &self.recording {
+            Some(recording) => recording.save(),
+            None => Ok(()),
+        }
+    }
+
+    // frame number used to timestamp recorded input, and to schedule played back input
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+    }
+
+    // measures time in frames, rather than by the wall clock, so key presses last the same number
+    // of frames however fast emulation runs
+    pub fn use_frame_clock(&mut self) {
+        self.frame_clock = Some(Instant::now());
+    }
+
+    pub fn now(&self) -> Instant {
+        match self.frame_clock {
+            Some(start) => start + FRAME_INTERVAL * self.frame as u32,
+            None => Instant::now(),
+        }
+    }
+
     pub fn listen(&mut self) {
+        if self.listener.is_some() {
+            return; // already listening
+        }
+
         let tx = self.key_press_tx.clone();
+        let focus_tx = self.focus_tx.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+
+        let handle = thread::spawn(move || {
+            // poll with a timeout, so the thread notices when it's stopped, rather than blocking
+            // on (and swallowing) the next key press
+            while !thread_stop.load(Ordering::Relaxed) {
+                if !event::poll(LISTENER_POLL_INTERVAL).unwrap() {
+                    continue;
+                }
 
-        thread::spawn(move || loop {
-            let ev = event::read().unwrap();
-            if let event::Event::Key(key) = ev {
-                match key.code {
-                    event::KeyCode::Char(_) => {
-                        tx.send((key.code, Instant::now())).unwrap();
+                match event::read().unwrap() {
+                    event::Event::Key(key) => {
+                        let code = match key.code {
+                            event::KeyCode::Char('c')
+                                if key.modifiers.contains(event::KeyModifiers::CONTROL) =>
+                            {
+                                CTRL_C
+                            }
+                            code => code,
+                        };
+
+                        match code {
+                            event::KeyCode::Char(_)
+                            | event::KeyCode::Esc
+                            | event::KeyCode::Enter
+                            | event::KeyCode::Backspace
+                            | event::KeyCode::Tab
+                            | event::KeyCode::F(_)
+                            | event::KeyCode::Up
+                            | event::KeyCode::Down
+                            | event::KeyCode::Left
+                            | event::KeyCode::Right => {
+                                if tx.send((code, Instant::now())).is_err() {
+                                    break; // keyboard dropped
+                                }
+                            }
+                            _ => (),
+                        }
                     }
-                    event::KeyCode::Esc => {
-                        tx.send((key.code, Instant::now())).unwrap();
-                        break;
+                    // only reported if the display asked the terminal to
+                    event::Event::FocusGained => {
+                        if focus_tx.send(true).is_err() {
+                            break;
+                        }
+                    }
+                    event::Event::FocusLost => {
+                        if focus_tx.send(false).is_err() {
+                            break;
+                        }
                     }
                     _ => (),
                 }
             }
         });
+
+        self.listener = Some((handle, stop));
+    }
+
+    pub fn exit(&mut self) {
+        if let Some((handle, stop)) = self.listener.take() {
+            stop.store(true, Ordering::Relaxed);
+            handle.join().expect("Keyboard listener thread panicked");
+        }
+        if let Some(gamepad) = &mut self.gamepad {
+            gamepad.close();
+        }
+    }
+
+    // next key press event, either from the terminal or from input playback
+    fn next_event(&mut self) -> Result<(event::KeyCode, Instant), TryRecvError> {
+        let now = self.now();
+        if let Some(playback) = &mut self.playback {
+            // live input is ignored during playback, except for quitting
+            loop {
+                match self.key_press_rx.try_recv() {
+                    Ok((key @ (event::KeyCode::Esc | CTRL_C), timestamp)) => {
+                        return Ok((key, timestamp))
+                    }
+                    Ok(_) => continue,
+                    Err(TryRecvError::Empty) => break,
+                    Err(err) => return Err(err),
+                }
+            }
+
+            return match playback.next_event(self.frame) {
+                Some(key) => Ok((key, now)),
+                None => Err(TryRecvError::Empty),
+            };
+        }
+
+        let (key, timestamp) = self.key_press_rx.try_recv()?;
+        if let Some(recording) = &mut self.recording {
+            recording.push(self.frame, key);
+        }
+
+        Ok((key, timestamp))
+    }
+
+    // pauses while the terminal isn't focused, unless already paused by the user
+    fn process_focus_changes(&mut self) {
+        while let Ok(gained) = self.focus_rx.try_recv() {
+            if !gained && !self.pause_toggle_on {
+                self.pause_toggle_on = true;
+                self.focus_paused = true;
+            } else if gained && self.focus_paused {
+                self.pause_toggle_on = false;
+                self.focus_paused = false;
+            }
+        }
+    }
+
+    // keys newly pressed on the gamepad and input pipe, if any, which are marked as pressed
+    fn poll_devices(&mut self) -> Vec<u8> {
+        let mut pressed = vec![];
+        if let Some(gamepad) = &mut self.gamepad {
+            pressed.extend(gamepad.poll());
+        }
+        if let Some(input_pipe) = &mut self.input_pipe {
+            pressed.extend(input_pipe.poll());
+        }
+
+        let now = Instant::now();
+        for key in &pressed {
+            self.pressed_keys.insert(*key, now);
+        }
+        pressed
     }
 
     pub fn process_pressed_keys(&mut self) {
+        self.process_focus_changes();
+        self.poll_devices();
+
         loop {
-            match self.key_press_rx.try_recv() {
+            match self.next_event() {
                 Ok((key, timestamp)) => match key {
-                    event::KeyCode::Char(ch) => {
-                        if ch == ' ' {
-                            self.pause_toggle_on = !self.pause_toggle_on;
-                            break;
-                        } else if let Some(hex_key) = self.key_map.get(&ch) {
-                            self.pressed_keys.insert(*hex_key, timestamp);
-                        }
+                    CTRL_C => {
+                        self.ctrl_c_pressed = true;
+                        self.esc_pressed = true;
+                        break;
                     }
-                    event::KeyCode::Esc => {
+                    _ if self.any_key_quits => {
                         self.esc_pressed = true;
                         break;
                     }
-                    _ => (),
+                    _ if self.debug_prompt.is_some() => self.handle_prompt_key(key),
+                    event::KeyCode::F(3) if self.debug_keys => {
+                        self.debug_prompt = Some(String::new());
+                    }
+                    event::KeyCode::Esc => {
+                        if self.esc_confirmed(timestamp) {
+                            self.esc_pressed = true;
+                            break;
+                        }
+                    }
+                    _ if self.handle_hotkey(key) => break,
+                    _ => {
+                        if let Some(hex_key) = self.hex_key(key) {
+                            self.pressed_keys.insert(hex_key, timestamp);
+                        }
+                    }
                 },
                 Err(TryRecvError::Empty) => break,
                 Err(TryRecvError::Disconnected) => panic!("Keyboard event receiver disconnected"),
@@ -97,26 +481,42 @@ impl Keyboard {
     }
 
     pub fn get_next_key(&mut self, valid_after: Instant) -> Option<u8> {
+        self.process_focus_changes();
+        // the first key is the one taken, but any others pressed with it are held too
+        if let Some(key) = self.poll_devices().first() {
+            return Some(*key);
+        }
+
         loop {
-            match self.key_press_rx.try_recv() {
+            match self.next_event() {
                 Ok((key, timestamp)) => match key {
-                    event::KeyCode::Char(ch) => {
-                        if timestamp < valid_after {
-                            continue;
-                        }
-                        if let Some(val) = self.key_map.get(&ch) {
-                            return Some(*val);
-                        }
-                        if ch == ' ' {
-                            self.pause_toggle_on = !self.pause_toggle_on;
-                            return None;
-                        }
+                    CTRL_C => {
+                        self.ctrl_c_pressed = true;
+                        self.esc_pressed = true;
+                        return None;
                     }
-                    event::KeyCode::Esc => {
+                    _ if self.any_key_quits => {
                         self.esc_pressed = true;
                         return None;
                     }
-                    _ => continue,
+                    _ if self.debug_prompt.is_some() => self.handle_prompt_key(key),
+                    event::KeyCode::F(3) if self.debug_keys => {
+                        self.debug_prompt = Some(String::new());
+                    }
+                    event::KeyCode::Esc => {
+                        if self.esc_confirmed(timestamp) {
+                            self.esc_pressed = true;
+                            return None;
+                        }
+                    }
+                    _ if timestamp < valid_after => continue,
+                    _ if self.handle_hotkey(key) => return None,
+                    _ => {
+                        if let Some(val) = self.hex_key(key) {
+                            self.pressed_keys.insert(val, timestamp);
+                            return Some(val);
+                        }
+                    }
                 },
                 Err(TryRecvError::Empty) => return None,
                 Err(TryRecvError::Disconnected) => panic!("Keyboard event receiver disconnected"),
@@ -124,11 +524,259 @@ impl Keyboard {
         }
     }
 
+    // true if the key was a hotkey, which ends the batch of events so its toggle shows at once
+    fn handle_hotkey(&mut self, key: event::KeyCode) -> bool {
+        match key {
+            _ if key == self.pause_key => self.toggle_pause(),
+            event::KeyCode::Char('m') => self.muted = !self.muted,
+            event::KeyCode::Char('i') => self.inverted = !self.inverted,
+            event::KeyCode::Char('l') => self.scanlines = !self.scanlines,
+            event::KeyCode::Char('g') => self.gif_toggle_on = !self.gif_toggle_on,
+            event::KeyCode::Char('t') => self.turbo_toggle_on = !self.turbo_toggle_on,
+            event::KeyCode::Char('o') => self.toggle_quirks(),
+            event::KeyCode::Char('?') | event::KeyCode::F(1) => self.toggle_help(),
+            event::KeyCode::F(num) if self.debug_keys => self.debug_presses.push(num),
+            _ => return false,
+        }
+        true
+    }
+
+    fn toggle_pause(&mut self) {
+        // the key map HUD's pause key still resumes when started paused
+        if self.key_map_hud && !self.pause_toggle_on {
+            self.hud_toggle_on = !self.hud_toggle_on;
+            return;
+        }
+        self.pause_toggle_on = !self.pause_toggle_on;
+        self.help_on = false;
+        self.quirks_on = false;
+        self.focus_paused = false; // the user's choice from now on
+    }
+
+    // pauses while the help is shown, and resumes when it's hidden
+    fn toggle_help(&mut self) {
+        self.help_on = !self.help_on;
+        self.quirks_on = false;
+        self.pause_toggle_on = self.help_on;
+    }
+
+    fn toggle_quirks(&mut self) {
+        self.quirks_on = !self.quirks_on;
+        self.help_on = false;
+        self.pause_toggle_on = self.quirks_on;
+    }
+
+    // front-end hotkeys and their actions, for the help overlay
+    pub fn hotkeys(&self) -> Vec<(String, &'static str)> {
+        let mut hotkeys = vec![
+            (
+                key_name(self.pause_key),
+                if self.key_map_hud {
+                    "show / hide key map"
+                } else {
+                    "pause / show key map"
+                },
+            ),
+            ("? / f1".to_string(), "show this help"),
+            ("o".to_string(), "show quirk settings"),
+            ("m".to_string(), "mute / unmute sound"),
+            ("i".to_string(), "invert colors"),
+            ("l".to_string(), "scanlines"),
+            ("g".to_string(), "start / stop recording a GIF"),
+            ("t".to_string(), "turbo, 4x speed, without sound"),
+            (
+                "esc".to_string(),
+                if self.confirm_quit {
+                    "main menu, when pressed twice"
+                } else {
+                    "main menu"
+                },
+            ),
+            ("ctrl-c".to_string(), "quit"),
+        ];
+        if self.debug_keys {
+            hotkeys.extend([
+                ("f2".to_string(), "toggle coordinate ruler"),
+                ("f3".to_string(), "debug prompt, e.g. set v3 2A"),
+                ("f5".to_string(), "halt / resume"),
+                ("f6".to_string(), "step a frame"),
+                ("f7".to_string(), "step an instruction"),
+                ("f4".to_string(), "step back an instruction"),
+                ("f8".to_string(), "dump memory to a file"),
+                ("f9".to_string(), "warm reset, keeping memory"),
+                ("f10".to_string(), "cold reset"),
+            ]);
+        }
+        hotkeys
+    }
+
+    // the CHIP-8 key a terminal key is mapped to, if any
+    fn hex_key(&self, key: event::KeyCode) -> Option<u8> {
+        self.key_map.get(&key).copied()
+    }
+
+    pub fn any_key_pressed(&self) -> bool {
+        (0..0x10).any(|key_val| self.is_key_pressed(key_val))
+    }
+
     pub fn is_key_pressed(&self, key_val: u8) -> bool {
+        if let Some(gamepad) = &self.gamepad {
+            if gamepad.is_key_held(key_val) {
+                return true;
+            }
+        }
+        if let Some(input_pipe) = &self.input_pipe {
+            if input_pipe.is_key_held(key_val) {
+                return true;
+            }
+        }
+
         if let Some(last_press) = self.pressed_keys.get(&key_val) {
-            last_press.elapsed() < KEY_PRESS_TTL
+            self.now().saturating_duration_since(*last_press) < self.key_press_ttl
         } else {
             false
         }
     }
 }
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        self.exit();
+    }
+}
+
+// the COSMAC VIP keypad's layout on the left of a QWERTY keyboard, and optionally the arrow keys
+pub fn default_key_map(arrow_keys: bool) -> HashMap<event::KeyCode, u8> {
+    let mut key_map = [
+        ('1', 0x1),
+        ('2', 0x2),
+        ('3', 0x3),
+        ('4', 0xC),
+        ('q', 0x4),
+        ('w', 0x5),
+        ('e', 0x6),
+        ('r', 0xD),
+        ('a', 0x7),
+        ('s', 0x8),
+        ('d', 0x9),
+        ('f', 0xE),
+        ('z', 0xA),
+        ('x', 0x0),
+        ('c', 0xB),
+        ('v', 0xF),
+    ]
+    .into_iter()
+    .map(|(ch, val)| (event::KeyCode::Char(ch), val))
+    .collect::<HashMap<_, _>>();
+    if arrow_keys {
+        // most games use 2/4/6/8 as a directional pad; only keys outside the standard layout are
+        // added, so every hex key can still be pressed
+        key_map.extend([
+            (event::KeyCode::Up, 0x2),
+            (event::KeyCode::Left, 0x4),
+            (event::KeyCode::Down, 0x8),
+            (event::KeyCode::Right, 0x6),
+        ]);
+    }
+    key_map
+}
+
+// keys handled before the key map, besides the pause key, so bindings can't use them
+pub fn is_hotkey(key: event::KeyCode, debug_keys: bool) -> bool {
+    matches!(
+        key,
+        event::KeyCode::Esc
+            | CTRL_C
+            | event::KeyCode::Char('m' | 'i' | 'l' | 'g' | 't' | 'o' | '?')
+            | event::KeyCode::F(1)
+    ) || (debug_keys && matches!(key, event::KeyCode::F(_)))
+}
+
+pub fn key_name(key: event::KeyCode) -> String {
+    match key {
+        event::KeyCode::Backspace => "backspace".to_string(),
+        event::KeyCode::Enter => "enter".to_string(),
+        event::KeyCode::Tab => "tab".to_string(),
+        event::KeyCode::F(num) => format!("f{}", num),
+        event::KeyCode::Char(' ') => "space".to_string(),
+        CTRL_C => "ctrl-c".to_string(),
+        event::KeyCode::Char(ch) => ch.to_string(),
+        event::KeyCode::Esc => "esc".to_string(),
+        event::KeyCode::Up => "up".to_string(),
+        event::KeyCode::Down => "down".to_string(),
+        event::KeyCode::Left => "left".to_string(),
+        event::KeyCode::Right => "right".to_string(),
+        _ => "unknown".to_string(),
+    }
+}
+
+pub fn parse_key_name(name: &str) -> Option<event::KeyCode> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(event::KeyCode::Char(ch)),
+        _ => match name {
+            "space" => Some(event::KeyCode::Char(' ')),
+            "esc" => Some(event::KeyCode::Esc),
+            "ctrl-c" => Some(CTRL_C),
+            "up" => Some(event::KeyCode::Up),
+            "down" => Some(event::KeyCode::Down),
+            "left" => Some(event::KeyCode::Left),
+            "right" => Some(event::KeyCode::Right),
+            "backspace" => Some(event::KeyCode::Backspace),
+            "enter" => Some(event::KeyCode::Enter),
+            "tab" => Some(event::KeyCode::Tab),
+            _ => name
+                .strip_prefix('f')
+                .and_then(|num| num.parse::<u8>().ok())
+                .filter(|num| (1..=12).contains(num))
+                .map(event::KeyCode::F),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrow_keys_keep_every_hex_key_reachable() {
+        let keyboard = Keyboard::new(Duration::from_millis(100), true, event::KeyCode::Char(' '));
+        for key_val in 0..0x10 {
+            assert!(
+                keyboard.key_map.values().any(|val| *val == key_val),
+                "hex key {:X} has no key",
+                key_val
+            );
+        }
+        assert_eq!(keyboard.hex_key(event::KeyCode::Up), Some(0x2));
+        assert_eq!(keyboard.hex_key(event::KeyCode::Char('w')), Some(0x5));
+    }
+
+    #[test]
+    fn wasd_keys_keep_every_hex_key_reachable() {
+        let mut keyboard =
+            Keyboard::new(Duration::from_millis(100), false, event::KeyCode::Char(' '));
+        keyboard.use_wasd_keys();
+        let labels = keyboard.key_labels();
+        assert_eq!(labels[0x2], "w");
+        assert_eq!(labels[0x4], "a");
+        assert_eq!(labels[0x6], "d");
+        assert_eq!(labels[0x8], "s");
+        assert_eq!(labels[0x5], "2");
+        assert_eq!(labels[0x7], "q");
+        assert_eq!(labels[0x9], "e");
+        assert!(labels.iter().all(|label| label.len() == 1));
+    }
+
+    #[test]
+    fn key_labels_follow_arrows_and_bindings() {
+        let mut keyboard =
+            Keyboard::new(Duration::from_millis(100), true, event::KeyCode::Char(' '));
+        keyboard.bind_keys(&[(event::KeyCode::Char('q'), 0x5)]);
+        let labels = keyboard.key_labels();
+        assert_eq!(labels[0x2], "2/up");
+        assert_eq!(labels[0x4], "left"); // q was moved to 5
+        assert_eq!(labels[0x5], "q/w");
+        assert_eq!(labels[0xF], "v");
+    }
+}
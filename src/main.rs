@@ -1,20 +1,108 @@
+mod asm;
+mod bench;
+mod capture;
+mod collisions;
 mod cpu;
+mod debugger;
+mod diff;
+mod disasm;
 mod display;
+mod error;
+mod gamepad;
+mod idle;
+mod input_pipe;
 mod keyboard;
+mod options;
+mod recording;
+mod rom;
+mod selftest;
+mod settings;
+mod state;
+mod stats;
+mod timing;
+mod trace;
+mod undo;
 
+use collisions::CollisionLog;
 use cpu::Cpu;
 use display::Display;
+use error::{Error, Result};
+use gamepad::Gamepad;
+use input_pipe::InputPipe;
 use keyboard::Keyboard;
+use options::{Command, Options, UnknownOpcodeBehavior, SIDECAR_EXTENSION, USAGE};
+use recording::{InputPlayback, InputRecording};
+use settings::Settings;
+use state::CpuState;
+use timing::TimingLog;
+use trace::Trace;
 
-use crossterm::{cursor, style, terminal};
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+    style::{self, Color, Stylize},
+    terminal,
+};
 use std::{
-    fs,
+    cmp, fs,
     io::{self, Write},
-    path::Path,
+    path::{Path, PathBuf},
     process,
+    time::Duration,
 };
 
-fn main() -> Result<(), io::Error> {
+// color themes the settings screen cycles through
+const COLORS: [&str; 6] = ["green", "white", "yellow", "cyan", "magenta", "red"];
+const SPEED_STEP: u32 = 100;
+
+const ATTRACT_IDLE: Duration = Duration::from_secs(30);
+// draws random hex digits at random positions, about 20 a second
+const ATTRACT_DEMO: [u8; 22] = [
+    0xC0, 0x3F, // V0 = random x
+    0xC1, 0x1F, // V1 = random y
+    0xC2, 0x0F, // V2 = random digit
+    0xF2, 0x29, // I = sprite for V2
+    0xD0, 0x15, // draw
+    0x63, 0x03, // V3 = 3
+    0xF3, 0x15, // DT = V3
+    0xF4, 0x07, // V4 = DT
+    0x34, 0x00, // skip the next instruction if V4 = 0
+    0x12, 0x0E, // wait for DT again
+    0x12, 0x00, // next digit
+];
+
+fn main() {
+    let options = Options::from_env().unwrap_or_else(|err| {
+        eprintln!("{}\n\n{}", err, USAGE);
+        process::exit(1);
+    });
+    if options.show_help {
+        println!("{}", USAGE);
+        return;
+    }
+    let result = match &options.command {
+        Command::Run => run_menu(options),
+        Command::Bench(rom_path) => bench::run(rom_path.clone(), &options),
+        Command::Diff(rom_path, inputs_path) => {
+            diff::run(rom_path.clone(), inputs_path.clone(), &options)
+        }
+        Command::SelfTest => selftest::run(&options),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(err.exit_code());
+    }
+}
+
+// picks ROMs to run until the user quits
+fn run_menu(mut options: Options) -> Result<()> {
+    let mut settings = Settings::load();
+    // a saved choice that's no longer valid, e.g. edited by hand, is ignored
+    if let Ok(saved) = options.with_defaults(settings.option_args()) {
+        options = saved;
+    }
+    let mut input_pipe: Option<(PathBuf, InputPipe)> = None; // opened once, and shared by ROMs
+
     loop {
         // check for ROMS dir
         let roms_path = Path::new("./roms");
@@ -26,76 +114,578 @@ Please add a folder named \"roms\" containing your ROMs to the same folder as th
             process::exit(1);
         }
 
-        let file_names = fs::read_dir(roms_path)?
+        let mut file_names = fs::read_dir(roms_path)?
             .flatten() // remove Errs
             .filter(|dir_entry| match dir_entry.file_type() {
                 // collect only files
                 Ok(file_type) => file_type.is_file(),
                 Err(_) => false,
             })
-            .map(|dir| dir.file_name())
+            // ROM options files
+            .filter(|dir_entry| {
+                !dir_entry
+                    .file_name()
+                    .to_string_lossy()
+                    .ends_with(SIDECAR_EXTENSION)
+            })
+            .flat_map(|dir_entry| {
+                // each ROM in an archive is listed by its path through it, e.g. "games.zip/pong.ch8"
+                if dir_entry.path().extension() != Some("zip".as_ref()) {
+                    return vec![dir_entry.file_name()];
+                }
+                let entries = fs::File::open(dir_entry.path())
+                    .map_err(Error::from)
+                    .and_then(rom::zip_entries)
+                    .unwrap_or_default(); // unreadable archives aren't listed
+                entries
+                    .iter()
+                    .filter(|entry| !entry.ends_with(SIDECAR_EXTENSION))
+                    .map(|entry| {
+                        Path::new(&dir_entry.file_name())
+                            .join(entry)
+                            .into_os_string()
+                    })
+                    .collect()
+            })
             .collect::<Vec<_>>();
+        file_names.sort(); // paged through, so in a predictable order
+
+        if file_names.is_empty() {
+            prompt_no_roms(&options)?;
+            continue;
+        }
 
         // ROM selection
-        let rom_idx = prompt_rom_selection(&file_names)?;
+        let rom_idx = prompt_rom_selection(&file_names, &mut settings, &mut options)?;
 
         let file_name = &file_names[rom_idx];
         let rom_path = roms_path.join(file_name);
 
+        settings.last_rom = Some(file_name.to_string_lossy().into_owned());
+        if let Err(err) = settings.save() {
+            eprintln!("Failed to save settings: {}", err);
+        }
+
+        let options = options
+            .for_rom(&rom_path)
+            .and_then(|options| options.for_variant())
+            .unwrap_or_else(|err| {
+                eprintln!("{}", err);
+                process::exit(1);
+            });
+        // a ROM that can't be read, e.g. assembly with a typo, is reported, then it's back to the
+        // menu to pick another
+        if let Err(err) = rom::read(&rom_path, options.load_addr) {
+            eprintln!("{}", err);
+            wait_for_key()?;
+            continue;
+        }
+
+        if options.rom_info && !confirm_rom_info(&rom_path, &options)? {
+            continue;
+        }
+        if options.preflight && !confirm_preflight(&rom_path, &options)? {
+            continue;
+        }
+
         // start up CHIP-8
-        let display = Display::new();
-        let keyboard = Keyboard::new();
-        let mut cpu = Cpu::new(display, keyboard);
+        let display = Display::new(&options);
+        let mut keyboard = Keyboard::new(options.key_ttl, options.arrow_keys, options.pause_key);
+        keyboard.muted = options.mute;
+        keyboard.inverted = options.invert;
+        keyboard.scanlines = options.scanlines;
+        if options.wasd_keys {
+            keyboard.use_wasd_keys();
+        }
+        keyboard.bind_keys(&options.key_bindings);
+        if options.key_map_hud {
+            keyboard.use_key_map_hud();
+        }
+        if options.start_paused {
+            keyboard.start_paused();
+        }
+        if options.confirm_quit {
+            keyboard.confirm_quit();
+        }
+        if options.debug {
+            keyboard.enable_debug_keys();
+        }
+        if let Some(path) = &options.gamepad_path {
+            keyboard.use_gamepad(Gamepad::open(path, options.gamepad_map.clone())?);
+        }
+        if let Some(path) = &options.input_pipe_path {
+            let pipe = match input_pipe.take() {
+                Some((open_path, pipe)) if &open_path == path => pipe,
+                _ => InputPipe::open(path)?,
+            };
+            keyboard.use_input_pipe(pipe.clone());
+            input_pipe = Some((path.clone(), pipe));
+        }
+        if let Some(path) = &options.record_path {
+            keyboard.record_to(InputRecording::new(path.clone()));
+        }
+        if let Some(path) = &options.replay_path {
+            keyboard.play_back(InputPlayback::load(path)?);
+        }
+        let mut cpu = Cpu::new(display, keyboard, &options);
+        if let Some(path) = &options.load_state_path {
+            cpu.start_from(CpuState::load(path)?);
+        }
+        if let Some(path) = &options.trace_path {
+            cpu.trace_to(Trace::create(path, options.trace_format)?);
+        }
+        if let Some(path) = &options.timing_log_path {
+            cpu.timing_log_to(TimingLog::create(path)?);
+        }
+        if let Some(path) = &options.collision_log_path {
+            cpu.collision_log_to(CollisionLog::create(path)?);
+        }
+        if options.bell {
+            cpu.on_sound(|sound_on| {
+                if sound_on {
+                    print!("\x07");
+                    let _ = io::stdout().flush();
+                }
+            });
+        }
 
-        cpu.init(rom_path)?;
+        // the state is dumped before exiting on an error too, to see what led to it
+        let result = cpu.init(rom_path);
+        if let Some(path) = &options.dump_state_path {
+            cpu.state().save(path)?;
+        }
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            if !options.pause_on_exit {
+                process::exit(err.exit_code());
+            }
+        }
+        if cpu.exit_requested() {
+            process::exit(0);
+        }
+        if options.pause_on_exit {
+            wait_for_key()?;
+        }
     }
 }
 
-fn prompt_rom_selection(file_names: &Vec<std::ffi::OsString>) -> Result<usize, io::Error> {
-    let mut stdout = io::stdout();
+// waits for a key press after a ROM exits, so what it printed isn't cleared by the menu at once
+fn wait_for_key() -> Result<()> {
+    println!("\nPress any key to return to the menu");
+
+    terminal::enable_raw_mode()?;
+    let event = loop {
+        match event::read() {
+            Ok(Event::Key(key_event)) => break Ok(key_event),
+            Ok(_) => continue,
+            Err(err) => break Err(err),
+        }
+    };
+    terminal::disable_raw_mode()?;
+
+    if let KeyEvent {
+        code: KeyCode::Char('c'),
+        modifiers,
+        ..
+    } = event?
+    {
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            process::exit(0);
+        }
+    }
+
+    Ok(())
+}
+
+fn print_menu_header(stdout: &mut io::Stdout, title: &str) -> Result<()> {
+    crossterm::execute!(
+        stdout,
+        terminal::Clear(terminal::ClearType::All),
+        style::SetForegroundColor(style::Color::Green),
+        cursor::MoveTo(0, 0),
+    )?;
+
     let spacer = "=".repeat(64);
-    let header = " ".repeat(29) + "CHIP-8";
+    println!("{}", spacer);
+    for line in title.lines() {
+        // centered over the spacer
+        let indent = spacer.len().saturating_sub(line.chars().count()) / 2;
+        println!("{}{}", " ".repeat(indent), line);
+    }
+    println!("{}", spacer);
+
+    Ok(())
+}
+
+// shown in place of the menu while the ROMs folder is empty; only quitting is possible
+fn prompt_no_roms(options: &Options) -> Result<()> {
+    let mut stdout = io::stdout();
 
     let mut is_first_try = true;
     loop {
-        crossterm::execute!(
-            stdout,
-            terminal::Clear(terminal::ClearType::All),
-            style::SetForegroundColor(style::Color::Green),
-            cursor::MoveTo(0, 0),
-        )?;
+        print_menu_header(&mut stdout, &options.title)?;
+        println!(" No ROMs found.\n");
+        println!(" Copy your ROM files (e.g. pong.ch8) into the \"roms\" folder, next to");
+        println!(" this program, then start it again.");
+        println!("\n q = quit");
+        println!("{}", "=".repeat(64));
+        if !is_first_try {
+            println!("\nThere are no ROMs to select. Press q to quit.");
+        }
+        stdout.flush()?;
 
-        println!("{}", spacer);
-        println!("{}", header);
-        println!("{}", spacer);
-        println!(" Detected ROMs:\n");
-        for (idx, file_name) in file_names.iter().enumerate() {
-            println!("  [{}] {}", idx, file_name.to_string_lossy());
+        match read_menu_key(options)? {
+            Some(KeyCode::Char('q')) => process::exit(0),
+            Some(_) => is_first_try = false,
+            None => is_first_try = true,
         }
-        println!("\n q = quit");
-        println!("{}", spacer);
+    }
+}
+
+#[derive(PartialEq, Eq)]
+enum MenuRow {
+    Heading(&'static str),
+    Rom(usize), // index in the file names
+}
+
+// a page of ROMs at a time, fitting the terminal, with favorites pinned to the top; ROMs are picked
+// by moving a cursor, or by typing their number
+fn prompt_rom_selection(
+    file_names: &[std::ffi::OsString],
+    settings: &mut Settings,
+    options: &mut Options,
+) -> Result<usize> {
+    let mut stdout = io::stdout();
+    let spacer = "=".repeat(64);
+    let name = |idx: usize| file_names[idx].to_string_lossy().into_owned();
+
+    let last_rom_idx = settings
+        .last_rom
+        .as_ref()
+        .and_then(|last_rom| (0..file_names.len()).find(|idx| name(*idx) == *last_rom));
+    let mut selected = last_rom_idx.unwrap_or(0);
+    let mut scroll = 0; // index of the first row shown
+    let mut typed_num = String::new();
+    loop {
+        let (favorites, others): (Vec<_>, Vec<_>) =
+            (0..file_names.len()).partition(|idx| settings.favorites.contains(&name(*idx)));
+        let mut rows = vec![];
+        if !favorites.is_empty() {
+            rows.push(MenuRow::Heading("Favorites"));
+            rows.extend(favorites.iter().map(|idx| MenuRow::Rom(*idx)));
+            rows.push(MenuRow::Heading("Others"));
+        }
+        rows.extend(others.iter().map(|idx| MenuRow::Rom(*idx)));
+        // ROMs in the order shown, as numbered
+        let order = favorites.iter().chain(&others).copied().collect::<Vec<_>>();
+        let cursor = order.iter().position(|idx| *idx == selected).unwrap_or(0);
+
+        // lines taken by everything but the rows: header, list heading, key help, typed number,
+        // spacer and the terminal's cursor
+        let other_lines = options.title.lines().count() + 2 + 2 + 3 + 2 + 1 + 1;
+        let term_rows = terminal::size().map(|(_, rows)| rows).unwrap_or(24) as usize;
+        let page_len = term_rows.saturating_sub(other_lines).max(1);
+        let cursor_row = rows
+            .iter()
+            .position(|row| *row == MenuRow::Rom(selected))
+            .unwrap_or(0);
+        if cursor_row < scroll {
+            // keep the section heading in view, when at the top of a section
+            scroll = match cursor_row.checked_sub(1).map(|prev| &rows[prev]) {
+                Some(MenuRow::Heading(_)) => cursor_row - 1,
+                _ => cursor_row,
+            };
+        } else if cursor_row >= scroll + page_len {
+            scroll = cursor_row + 1 - page_len;
+        }
+        let page_end = cmp::min(scroll + page_len, rows.len());
 
-        if is_first_try {
-            println!("\nSelect a ROM:");
-            is_first_try = false;
-        } else {
-            println!("\nInvalid selection. Please enter the number for a ROM, and press ENTER.")
+        print_menu_header(&mut stdout, &options.title)?;
+        println!(" Detected ROMs ({}):\n", file_names.len());
+        for row in &rows[scroll..page_end] {
+            let idx = match row {
+                MenuRow::Heading(heading) => {
+                    println!(" {}:", heading);
+                    continue;
+                }
+                MenuRow::Rom(idx) => *idx,
+            };
+            let num = order.iter().position(|rom| *rom == idx).unwrap_or(0);
+            let last_played = if Some(idx) == last_rom_idx {
+                "  (last played)"
+            } else {
+                ""
+            };
+            let line = format!(" [{}] {}{}", num, name(idx), last_played);
+            if idx == selected {
+                println!(" >{}", line.reverse());
+            } else {
+                println!("  {}", line);
+            }
+        }
+        println!("\n up/down or j/k = move, PgUp/PgDn = page, ENTER = launch");
+        println!(" number + ENTER = launch that ROM, f = (un)favorite, s = settings, q = quit");
+        println!("{}", spacer);
+        if !typed_num.is_empty() {
+            println!("\n> {}", typed_num);
         }
-        print!("> ");
         stdout.flush()?;
 
-        let stdin = io::stdin();
-        let mut raw_input = String::new();
-        stdin.read_line(&mut raw_input)?;
-        let input = raw_input.trim();
+        let last_pos = order.len() - 1;
+        let move_to = |pos: usize| order[cmp::min(pos, last_pos)];
+        match read_menu_key(options)? {
+            Some(KeyCode::Up | KeyCode::Char('k')) => selected = move_to(cursor.saturating_sub(1)),
+            Some(KeyCode::Down | KeyCode::Char('j')) => selected = move_to(cursor + 1),
+            Some(KeyCode::PageUp) => selected = move_to(cursor.saturating_sub(page_len)),
+            Some(KeyCode::PageDown) => selected = move_to(cursor + page_len),
+            Some(KeyCode::Home) => selected = move_to(0),
+            Some(KeyCode::End) => selected = move_to(last_pos),
+            Some(KeyCode::Char(digit)) if digit.is_ascii_digit() => typed_num.push(digit),
+            Some(KeyCode::Backspace) => {
+                typed_num.pop();
+            }
+            Some(KeyCode::Esc) => typed_num.clear(),
+            Some(KeyCode::Enter) if typed_num.is_empty() => return Ok(selected),
+            Some(KeyCode::Enter) => match typed_num.parse::<usize>() {
+                Ok(num) if num < order.len() => return Ok(order[num]),
+                _ => typed_num.clear(), // no such ROM
+            },
+            Some(KeyCode::Char('f')) => {
+                let rom = name(selected);
+                match settings.favorites.iter().position(|fav| *fav == rom) {
+                    Some(pos) => {
+                        settings.favorites.remove(pos);
+                    }
+                    None => settings.favorites.push(rom),
+                }
+                if let Err(err) = settings.save() {
+                    eprintln!("Failed to save settings: {}", err);
+                }
+            }
+            Some(KeyCode::Char('s')) => prompt_settings(options, settings)?,
+            Some(KeyCode::Char('q')) => process::exit(0),
+            _ => (), // including after attract mode, or a resize, which just redraw the menu
+        }
+    }
+}
+
+// lets the user change common options without knowing the flags, until they go back to the menu;
+// the speed and color chosen are saved for the next run
+fn prompt_settings(options: &mut Options, settings: &mut Settings) -> Result<()> {
+    const LABELS: [&str; 5] = [
+        "Speed",
+        "Display wait quirk",
+        "Jump VX quirk",
+        "Color",
+        "Sound",
+    ];
 
-        if input == "q" {
-            process::exit(0);
+    let mut stdout = io::stdout();
+    let on_off = |on: bool| if on { "on" } else { "off" };
+    let mut selected = 0;
+    loop {
+        let sound_on = !options.mute;
+        let color_idx = COLORS
+            .iter()
+            .position(|name| Color::try_from(*name).ok() == Some(options.color));
+        let values = [
+            format!("< {} >", options.speed),
+            on_off(options.quirks.display_wait).to_string(),
+            on_off(options.quirks.jump_vx).to_string(),
+            format!("< {} >", color_idx.map_or("custom", |idx| COLORS[idx])),
+            on_off(sound_on).to_string(),
+        ];
+
+        print_menu_header(&mut stdout, &options.title)?;
+        println!(" Settings:\n");
+        for (idx, (label, value)) in LABELS.iter().zip(&values).enumerate() {
+            let line = format!(" {:<20}{}", label, value);
+            if idx == selected {
+                println!(" >{}", line.reverse());
+            } else {
+                println!("  {}", line);
+            }
         }
-        if let Ok(num) = input.parse::<usize>() {
-            if num < file_names.len() {
-                return Ok(num);
+        println!("\n up/down or j/k = move, left/right or ENTER = change, b or ESC = back");
+        println!("{}", "=".repeat(64));
+        stdout.flush()?;
+
+        let forward = match read_menu_key(options)? {
+            Some(KeyCode::Up | KeyCode::Char('k')) => {
+                selected = selected.saturating_sub(1);
+                continue;
             }
+            Some(KeyCode::Down | KeyCode::Char('j')) => {
+                selected = cmp::min(selected + 1, LABELS.len() - 1);
+                continue;
+            }
+            Some(KeyCode::Right | KeyCode::Enter) => true,
+            Some(KeyCode::Left) => false,
+            Some(KeyCode::Char('b') | KeyCode::Esc) => return Ok(()),
+            _ => continue,
+        };
+        let changed = match selected {
+            0 => {
+                let speed = match forward {
+                    true => options.speed + SPEED_STEP,
+                    false => options.speed.saturating_sub(SPEED_STEP).max(SPEED_STEP),
+                };
+                options.with_value("--speed", &speed.to_string())
+            }
+            1 => options.with_flag("--display-wait", !options.quirks.display_wait),
+            2 => options.with_flag("--jump-vx", !options.quirks.jump_vx),
+            3 => {
+                let idx = match (color_idx, forward) {
+                    (Some(idx), true) => (idx + 1) % COLORS.len(),
+                    (Some(idx), false) => (idx + COLORS.len() - 1) % COLORS.len(),
+                    (None, _) => 0,
+                };
+                options.with_value("--color", COLORS[idx])
+            }
+            _ => options.with_flag("--mute", sound_on),
+        };
+        // unchanged if invalid, e.g. past the speed limit
+        if let Ok(changed) = changed {
+            *options = changed;
         }
+
+        // speed and color are remembered for the next run
+        match selected {
+            0 => settings.speed = Some(options.speed),
+            3 => {
+                settings.color = COLORS
+                    .iter()
+                    .find(|name| Color::try_from(**name).ok() == Some(options.color))
+                    .map(|name| name.to_string())
+            }
+            _ => continue,
+        }
+        if let Err(err) = settings.save() {
+            eprintln!("Failed to save settings: {}", err);
+        }
+    }
+}
+
+// shows details about the selected ROM, and returns whether the user wants to launch it
+fn confirm_rom_info(rom_path: &Path, options: &Options) -> Result<bool> {
+    let mut stdout = io::stdout();
+    let bytes = rom::read(rom_path, options.load_addr)?;
+
+    print_menu_header(&mut stdout, &options.title)?;
+    println!(
+        " ROM:     {}",
+        rom_path.file_name().unwrap_or_default().to_string_lossy()
+    );
+    println!(" Size:    {} bytes", bytes.len());
+    println!(" CRC32:   {:08X}", rom::crc32(&bytes));
+    println!(
+        " Variant: {} (guessed, its quirks apply only if set with --variant)",
+        rom::guess_variant(&bytes)
+    );
+    println!("\n b = back");
+    println!("{}", "=".repeat(64));
+    println!("\nPress ENTER to launch:");
+    print!("> ");
+    stdout.flush()?;
+
+    let mut raw_input = String::new();
+    io::stdin().read_line(&mut raw_input)?;
+
+    Ok(raw_input.trim() != "b")
+}
+
+// warns about opcodes the ROM contains that the emulator doesn't implement, and returns whether the
+// user wants to launch it anyway
+fn confirm_preflight(rom_path: &Path, options: &Options) -> Result<bool> {
+    const MAX_LISTED: usize = 16;
+
+    let bytes = rom::read(rom_path, options.load_addr)?;
+    let unsupported = rom::unsupported_opcodes(&bytes, options.load_addr, options.memory_size);
+    if unsupported.is_empty() {
+        return Ok(true);
+    }
+
+    let mut stdout = io::stdout();
+    print_menu_header(&mut stdout, &options.title)?;
+    println!(" Unsupported opcodes found:\n");
+    for (opcode, addr, count) in unsupported.iter().take(MAX_LISTED) {
+        println!("  0x{:04X} at 0x{:03X} ({}x)", opcode, addr, count);
     }
+    if unsupported.len() > MAX_LISTED {
+        println!("  ...and {} more", unsupported.len() - MAX_LISTED);
+    }
+    match options.on_unknown {
+        UnknownOpcodeBehavior::Skip => println!("\n These are skipped if they run."),
+        UnknownOpcodeBehavior::Halt => println!("\n Emulation halts if one of these runs."),
+    }
+    println!(" Data can look like code, so some may never run.");
+    println!("\n b = back");
+    println!("{}", "=".repeat(64));
+    println!("\nPress ENTER to launch anyway:");
+    print!("> ");
+    stdout.flush()?;
+
+    let mut raw_input = String::new();
+    io::stdin().read_line(&mut raw_input)?;
+
+    Ok(raw_input.trim() != "b")
+}
+
+// waits for a key press in the menu; returns None when the menu should just be redrawn, e.g.
+// after the terminal was resized, or, in attract mode, a demo played as nothing was pressed
+fn read_menu_key(options: &Options) -> Result<Option<KeyCode>> {
+    // raw mode, so key presses are seen without ENTER
+    terminal::enable_raw_mode()?;
+    let event = match options.attract {
+        true => match event::poll(ATTRACT_IDLE) {
+            Ok(true) => event::read().map(Some),
+            Ok(false) => Ok(None),
+            Err(err) => Err(err),
+        },
+        false => event::read().map(Some),
+    };
+    terminal::disable_raw_mode()?;
+
+    match event? {
+        None => {
+            play_attract(options)?;
+            Ok(None)
+        }
+        Some(Event::Key(KeyEvent {
+            code: KeyCode::Char('c'),
+            modifiers,
+            ..
+        })) if modifiers.contains(KeyModifiers::CONTROL) => process::exit(0),
+        Some(Event::Key(KeyEvent { code, .. })) => Ok(Some(code)),
+        Some(_) => Ok(None),
+    }
+}
+
+// plays the attract mode demo until a key is pressed
+fn play_attract(options: &Options) -> Result<()> {
+    let mut options = options.clone();
+    options.cycles = None;
+    options.frames = None;
+    options.load_addr = 0x200; // the demo jumps to fixed addresses
+                               // a demo for anyone passing by, so it runs freely, and leaves the user's files alone
+    options.input_advance = false;
+    options.debug = false;
+    options.record_path = None;
+    options.replay_path = None;
+    options.trace_path = None;
+    options.timing_log_path = None;
+    options.collision_log_path = None;
+    options.dump_state_path = None;
+    options.load_state_path = None;
+
+    let mut keyboard = Keyboard::new(options.key_ttl, options.arrow_keys, options.pause_key);
+    keyboard.quit_on_any_key();
+    let mut cpu = Cpu::new(Display::new(&options), keyboard, &options);
+    cpu.run_program(&ATTRACT_DEMO)?;
+
+    if cpu.exit_requested() {
+        process::exit(0);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,943 @@
+use crate::display::{COLS, ROWS};
+use crate::gamepad::{self, GamepadInput};
+use crate::keyboard::{default_key_map, is_hotkey, key_name, parse_key_name, CTRL_C};
+use crate::rom::Variant;
+
+use crossterm::{event::KeyCode, style::Color};
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+// appended to a ROM's file name, for its options file, e.g. "pong.ch8.cfg"
+pub const SIDECAR_EXTENSION: &str = ".cfg";
+
+pub const USAGE: &str = "\
+Usage: rust-chip-8 [OPTIONS]
+       rust-chip-8 bench <ROM> [OPTIONS]
+       rust-chip-8 diff <ROM> <INPUTS> [OPTIONS]
+       rust-chip-8 --selftest [OPTIONS]
+
+Commands:
+  bench <ROM>               Run a ROM headless, as fast as possible, and report instructions per
+                            second
+  diff <ROM> <INPUTS>       Run a ROM headless with input recorded with --record, and compare
+                            each frame with the first run's, saved to <INPUTS>.frames
+  --selftest                Run a built-in diagnostic program, which checks arithmetic, drawing
+                            and the delay timer and shows the results, until ESC
+
+Options:
+  --speed <n>               Instructions per second, 60-1000000 (default: 700)
+  --variant <chip8|schip|xochip>
+                            Variant the ROM targets, which sets default quirks: --jump-vx for
+                            schip, --memory 65536 for xochip (default: chip8, as guessing from
+                            opcodes mistakes sprite data for them)
+  --on-unknown <skip|halt>  What to do on an unknown opcode (default: skip)
+  --on-empty-ret <error|ignore|halt>
+                            What to do on RET with an empty stack: stop with an error, carry
+                            on as some lenient interpreters do, or stop quietly (default: error)
+  --on-bcd-overflow <wrap|error>
+                            What to do when LD B, Vx would write past the end of memory: wrap
+                            around to address 0, or stop with an error (default: wrap)
+  --load-addr <hex>         Address the ROM is loaded at, and executed from (default: 200)
+  --memory <bytes>          Memory size, a power of 2 from 4096 to 65536 (default: 4096)
+                            XO-CHIP ROMs can address 65536
+  --init <zero|ff|random>   What memory and registers hold before the ROM sets them, to catch
+                            ROMs that depend on it; random uses --seed (default: zero)
+  --halt-on-self-jump       Stop when the ROM jumps to itself, as test ROMs do when done
+  --vip-timing              Charge each instruction its approximate COSMAC VIP cycle cost,
+                            at the VIP's clock speed, instead of using --speed
+  --wall-clock-dt           Count the delay timer down by the wall clock as instructions run, at
+                            exactly 60 Hz, rather than once a frame, for ROMs that poll it
+  --idle-skip               Skip the rest of the frame when the ROM busy-waits, e.g. for DT or
+                            a key, to save CPU; --cycles counts fewer instructions
+  --input-advance           Only run while a key is pressed, freezing the game and its timers
+                            in between, to think over puzzles (real-time games won't work)
+  --display-wait            Quirk: end the frame after each DRW, like the COSMAC VIP
+  --jump-vx                 Quirk: BXNN jumps to XNN + VX, like SUPER-CHIP, not NNN + V0
+  --key-ttl <ms>            How long a key press is held for, 20-1000 (default: 100)
+                            Raise it if keys don't stay down, lower it if they stick
+  --pause-key <key>         Key that pauses and shows the key map (default: space)
+                            e.g. backspace, tab or f2-f12 (not with --debug), but not a
+                            hotkey or a key mapped to a hex key
+  --start-paused            Load the ROM, but wait for the pause key before running it
+  --confirm-quit            Make Esc ask to be pressed again within a second before quitting
+                            the ROM, so a stray press doesn't lose progress
+  --key-map-hud             Make the pause key show the key map over the running game,
+                            instead of pausing it
+  --focus-pause             Pause while the terminal window isn't focused, on terminals that
+                            report focus changes
+  --arrows                  Also map the arrow keys to 2/4/6/8 (up/left/right/down)
+  --wasd                    Map W/A/S/D to 2/4/8/6 (up/left/down/right), moving 5, 7 and 9,
+                            which they'd take, to 2, Q and E, so every hex key stays mapped
+  --bind <bindings>         Map more keys to hex keys, by name as for --pause-key, e.g.
+                            up=2,k=2,enter=5; a key can only map to one hex key, but a hex
+                            key can have any number of keys; hotkeys like m and the pause key
+                            can't be bound
+  --gamepad <device>        Also read input from a Linux joystick device, e.g. /dev/input/js0
+  --gamepad-map <map>       Gamepad mappings to hex keys, overriding the defaults, e.g.
+                            button0=5,axis0-=4,axis0+=6 (default: stick and d-pad to 2/4/6/8,
+                            buttons 0-3 to 5/A/B/F)
+  --input-pipe <path>       Also read keys from a named pipe (e.g. made with mkfifo), a command
+                            per line: \"press 5\" holds hex key 5 until \"release 5\"
+  --fade                    Fade erased pixels out over a few frames, like a CRT's phosphor
+  --scanlines               Start with every other line dimmed, like a CRT's scanlines
+                            (L toggles them)
+  --invert                  Start with colors inverted, lit pixels drawn blank on a solid
+                            background (I toggles it)
+  --charset <blocks|ascii>  Characters pixels are drawn with (default: blocks)
+                            Use ascii on terminals with poor Unicode block support
+  --on-char <char>          Character lit pixels are drawn with in ascii (default: #)
+  --cell-width <1|2>        Characters per pixel, to match the terminal's aspect ratio
+                            (default: 2)
+  --scale <n>               Draw each pixel n times larger, 1-4 (default: 1)
+  --width <n>               Display width in pixels, for homebrew made for other sizes, 16-256
+                            (default: 64); it must fit the terminal
+  --height <n>              Display height in pixels, 16-256 (default: 32)
+  --color <color>           Color pixels are drawn in, by name (e.g. white) or as #rrggbb
+                            (default: green)
+  --frame-skip <n>          Render only every (n+1)th frame, 0-10, so slow terminals keep up
+                            (default: 0)
+  --no-alt-screen           Draw over the main screen, for consoles without alternate screen
+                            support (used automatically if entering it fails)
+  --bell                    Ring the terminal bell when a sound starts
+  --mute                    Start with sound muted (M toggles it)
+  --font <file>             Replace the built-in hex font with 80 bytes from a file
+  --persist-flags           Save SUPER-CHIP flag registers (e.g. high scores) between runs
+  --title <text>            Header of the ROM menu, centered, and with any number of lines,
+                            e.g. an ASCII-art banner (default: CHIP-8)
+  --attract                 Play a demo when the ROM menu is left idle for 30 seconds
+  --rom-info                Show ROM details, and ask for confirmation before launch
+  --preflight               Scan the ROM for unsupported opcodes, and ask before launching it
+  --pause-on-exit           Wait for a key after the ROM exits, before returning to the menu,
+                            so any error can be read; errors then return to the menu
+  --opcode-stats            Print how often each kind of opcode ran, after the ROM exits
+  --draw-rate               Show how many DRW and CLS instructions ran in the last second, over
+                            the top border, to see why a ROM flickers
+  --debug                   Enable debug keys: F2 toggles a coordinate ruler, F5 halts or
+                            resumes, F6 steps a frame, F7 steps an instruction, F4 steps
+                            back one (up to 10000), F8 dumps
+                            memory to memory-<frame>.bin, F9 restarts the ROM leaving memory
+                            as is (warm reset), F10 restarts it from scratch (cold reset),
+                            F3 opens a prompt to change state, e.g. set v3 2A, set i 300,
+                            set mem 250 FF, set pc 200, set dt 0 or set st 0 (hex numbers)
+  --cls-flash <color>       Fill the screen with a color for a frame whenever CLS runs, to make
+                            rapid clears visible, by name (e.g. red) or as #rrggbb
+  --slow-draw <ms>          Draw sprites a pixel at a time, waiting this long after each,
+                            1-1000, to watch where they land (far too slow to play)
+  --trace <file>            Log each executed instruction to a file, with DRW collision counts
+                            and writes below the load address, e.g. over the font
+  --trace-format <format>   notes (default) or state, which logs the registers before each
+                            instruction instead of notes, e.g. PC:0200 OP:6A02 V0:00 ... VF:00
+                            I:0000 SP:0 DT:00 ST:00, in fixed columns, so runs diff line by
+                            line, e.g. with different options or builds
+  --collision-log <file>    Log each DRW that erased pixels, setting VF, to a file, with its
+                            frame, address, I, position and number of pixels erased
+  --timing-log <file>       Log how long each frame spent executing, rendering and sleeping, in
+                            microseconds, to a CSV file, to find the cause of stutter
+  --record <file>           Record key presses, with their frame numbers, to a file
+  --replay <file>           Play back key presses recorded with --record
+  --cycles <n>              Stop after running this many instructions, then exit
+  --frames <n>              Stop after this many frames, then exit (diff default: 3600)
+  --dump-state-on-exit <file>
+                            Write registers, timers, stack and framebuffer as JSON on exit
+  --load-state <file>       Start the ROM from a state written with --dump-state-on-exit,
+                            e.g. to carry on a game (memory isn't saved, so ROMs that modify
+                            themselves may not carry on as they were)
+  --seed <n>                Seed for random numbers, for repeatable runs (diff defaults to 0)
+  --instructions <n>        Number of instructions to run with bench, unless --cycles is set
+                            (default: 10000000)
+  -h, --help                Print this help
+
+Options can also be set per ROM, in a file next to it named after it plus .cfg, e.g.
+roms/pong.ch8.cfg, with a line per option, as name = value, where the name is the option's
+without the dashes, and the value is the rest of the line, e.g. speed = 1000 or
+title = Pong!; options without values are set with true, e.g. display-wait = true, and
+left out with false. Blank lines, and lines starting with #, are skipped. Command-line
+options take precedence.
+
+Exit codes:
+  0  Quit, or a --cycles or --frames limit was reached
+  1  Any other error, e.g. invalid options, a missing file, or diff found a divergence
+  2  The ROM doesn't fit in memory
+  3  Unknown opcode, with --on-unknown halt
+  4  RET with an empty stack, with --on-empty-ret error
+  5  BCD store past the end of memory, with --on-bcd-overflow error
+  6  CALL with 16 subroutines already on the stack
+  7  The program counter ran past 0xFFFF
+  8  --selftest found a failing check";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UnknownOpcodeBehavior {
+    Skip, // ignore the opcode, and report it after the ROM exits
+    Halt, // stop emulation with an error
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum EmptyStackBehavior {
+    Error,  // stop emulation with an error
+    Ignore, // treat RET as a no-op
+    Halt,   // stop emulation, as if the ROM had ended
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MemoryOverflowBehavior {
+    Wrap,  // continue from address 0
+    Error, // stop emulation with an error
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Notes, // address and opcode, with notes of what the instruction did
+    State, // address, opcode and registers, in fixed columns, to diff runs
+}
+
+// what memory and registers hold before the program sets them
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum InitPattern {
+    Zero,
+    Ff,
+    Random,
+}
+
+// behavioral differences between CHIP-8 interpreters that ROMs may depend on
+#[derive(Clone, Copy)]
+pub struct Quirks {
+    pub display_wait: bool, // DRW waits for the vertical blank, i.e. the next frame
+    pub jump_vx: bool,      // BXNN jumps to XNN + VX, rather than NNN + V0
+}
+
+#[derive(Clone, Copy)]
+pub enum Charset {
+    Blocks,
+    Ascii(char), // "on" pixel character
+}
+
+#[derive(Clone)]
+pub enum Command {
+    Run,
+    Bench(PathBuf),
+    Diff(PathBuf, PathBuf), // ROM, input recording
+    SelfTest,
+}
+
+#[derive(Clone)]
+pub struct Options {
+    args: Vec<String>, // from the command line, to re-apply over per-ROM options
+    pub command: Command,
+    pub speed: u32,
+    pub variant: Option<Variant>, // its defaults only apply when set, never from a guess
+    pub on_unknown: UnknownOpcodeBehavior,
+    pub on_empty_ret: EmptyStackBehavior,
+    pub on_bcd_overflow: MemoryOverflowBehavior,
+    pub quirks: Quirks,
+    pub load_addr: u16,
+    pub memory_size: usize,
+    pub init_pattern: InitPattern,
+    pub halt_on_self_jump: bool,
+    pub vip_timing: bool,
+    pub idle_skip: bool,
+    pub input_advance: bool,
+    pub wall_clock_dt: bool,
+    pub key_ttl: Duration,
+    pub start_paused: bool,
+    pub confirm_quit: bool,
+    pub key_map_hud: bool,
+    pub arrow_keys: bool,
+    pub wasd_keys: bool,
+    pub key_bindings: Vec<(KeyCode, u8)>,
+    pub gamepad_path: Option<PathBuf>,
+    pub gamepad_map: HashMap<GamepadInput, u8>,
+    pub input_pipe_path: Option<PathBuf>,
+    pub pause_key: KeyCode,
+    pub focus_pause: bool,
+    pub fade: bool,
+    pub invert: bool,
+    pub scanlines: bool,
+    pub charset: Charset,
+    pub cell_width: usize,
+    pub width: usize,
+    pub height: usize,
+    pub scale: usize,
+    pub color: Color,
+    pub cls_flash: Option<Color>,
+    pub no_alt_screen: bool,
+    pub frame_skip: u8,
+    pub bell: bool,
+    pub mute: bool,
+    pub font_path: Option<PathBuf>,
+    pub persist_flags: bool,
+    pub rom_info: bool,
+    pub title: String,
+    pub attract: bool,
+    pub preflight: bool,
+    pub pause_on_exit: bool,
+    pub opcode_stats: bool,
+    pub draw_rate: bool,
+    pub debug: bool,
+    pub slow_draw: Option<Duration>,
+    pub trace_path: Option<PathBuf>,
+    pub trace_format: TraceFormat,
+    pub timing_log_path: Option<PathBuf>,
+    pub collision_log_path: Option<PathBuf>,
+    pub record_path: Option<PathBuf>,
+    pub replay_path: Option<PathBuf>,
+    pub cycles: Option<u64>,
+    pub dump_state_path: Option<PathBuf>,
+    pub load_state_path: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub frames: Option<u64>,
+    pub bench_instructions: u64,
+    pub selftest: bool,
+    pub show_help: bool,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Self {
+            args: vec![],
+            command: Command::Run,
+            speed: 700,
+            variant: None,
+            on_unknown: UnknownOpcodeBehavior::Skip,
+            on_empty_ret: EmptyStackBehavior::Error,
+            on_bcd_overflow: MemoryOverflowBehavior::Wrap,
+            quirks: Quirks {
+                display_wait: false,
+                jump_vx: false,
+            },
+            load_addr: 0x200,
+            memory_size: 4096,
+            init_pattern: InitPattern::Zero,
+            halt_on_self_jump: false,
+            vip_timing: false,
+            idle_skip: false,
+            input_advance: false,
+            wall_clock_dt: false,
+            key_ttl: Duration::from_millis(100),
+            start_paused: false,
+            confirm_quit: false,
+            key_map_hud: false,
+            arrow_keys: false,
+            wasd_keys: false,
+            key_bindings: vec![],
+            gamepad_path: None,
+            gamepad_map: gamepad::default_map(),
+            input_pipe_path: None,
+            pause_key: KeyCode::Char(' '),
+            focus_pause: false,
+            fade: false,
+            invert: false,
+            scanlines: false,
+            charset: Charset::Blocks,
+            cell_width: 2,
+            width: COLS,
+            height: ROWS,
+            scale: 1,
+            color: Color::Green,
+            cls_flash: None,
+            no_alt_screen: false,
+            frame_skip: 0,
+            bell: false,
+            mute: false,
+            font_path: None,
+            persist_flags: false,
+            rom_info: false,
+            title: "CHIP-8".to_string(),
+            attract: false,
+            preflight: false,
+            pause_on_exit: false,
+            opcode_stats: false,
+            draw_rate: false,
+            debug: false,
+            slow_draw: None,
+            trace_path: None,
+            trace_format: TraceFormat::Notes,
+            timing_log_path: None,
+            collision_log_path: None,
+            record_path: None,
+            replay_path: None,
+            cycles: None,
+            dump_state_path: None,
+            load_state_path: None,
+            seed: None,
+            frames: None,
+            bench_instructions: 10_000_000,
+            selftest: false,
+            show_help: false,
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(env::args().skip(1))
+    }
+
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Self::new();
+        options.args = args.collect();
+        let mut args = options.args.clone().into_iter();
+        let mut positionals = vec![];
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--speed" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.speed = val
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|ips| (60..=1_000_000).contains(ips))
+                        .ok_or_else(|| invalid_value(&arg, &val))?;
+                }
+                "--variant" => {
+                    options.variant = match next_value(&mut args, &arg)?.as_str() {
+                        "chip8" => Some(Variant::Chip8),
+                        "schip" => Some(Variant::SuperChip),
+                        "xochip" => Some(Variant::XoChip),
+                        val => return Err(invalid_value(&arg, val)),
+                    };
+                }
+                "--on-unknown" => {
+                    options.on_unknown = match next_value(&mut args, &arg)?.as_str() {
+                        "skip" => UnknownOpcodeBehavior::Skip,
+                        "halt" => UnknownOpcodeBehavior::Halt,
+                        val => return Err(invalid_value(&arg, val)),
+                    };
+                }
+                "--on-empty-ret" => {
+                    options.on_empty_ret = match next_value(&mut args, &arg)?.as_str() {
+                        "error" => EmptyStackBehavior::Error,
+                        "ignore" => EmptyStackBehavior::Ignore,
+                        "halt" => EmptyStackBehavior::Halt,
+                        val => return Err(invalid_value(&arg, val)),
+                    };
+                }
+                "--on-bcd-overflow" => {
+                    options.on_bcd_overflow = match next_value(&mut args, &arg)?.as_str() {
+                        "wrap" => MemoryOverflowBehavior::Wrap,
+                        "error" => MemoryOverflowBehavior::Error,
+                        val => return Err(invalid_value(&arg, val)),
+                    };
+                }
+                "--load-addr" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.load_addr = parse_hex(&val).ok_or_else(|| invalid_value(&arg, &val))?;
+                }
+                "--memory" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.memory_size = val
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|size| size.is_power_of_two() && (4096..=65536).contains(size))
+                        .ok_or_else(|| invalid_value(&arg, &val))?;
+                }
+                "--init" => {
+                    options.init_pattern = match next_value(&mut args, &arg)?.as_str() {
+                        "zero" => InitPattern::Zero,
+                        "ff" => InitPattern::Ff,
+                        "random" => InitPattern::Random,
+                        val => return Err(invalid_value(&arg, val)),
+                    };
+                }
+                "--halt-on-self-jump" => options.halt_on_self_jump = true,
+                "--vip-timing" => options.vip_timing = true,
+                "--idle-skip" => options.idle_skip = true,
+                "--input-advance" => options.input_advance = true,
+                "--wall-clock-dt" => options.wall_clock_dt = true,
+                "--display-wait" => options.quirks.display_wait = true,
+                "--jump-vx" => options.quirks.jump_vx = true,
+                "--key-ttl" => {
+                    let val = next_value(&mut args, &arg)?;
+                    let ms = val
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|ms| (20..=1000).contains(ms))
+                        .ok_or_else(|| invalid_value(&arg, &val))?;
+                    options.key_ttl = Duration::from_millis(ms);
+                }
+                "--pause-key" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.pause_key =
+                        parse_key_name(&val).ok_or_else(|| invalid_value(&arg, &val))?;
+                }
+                "--focus-pause" => options.focus_pause = true,
+                "--arrows" => options.arrow_keys = true,
+                "--wasd" => options.wasd_keys = true,
+                "--bind" => {
+                    let val = next_value(&mut args, &arg)?;
+                    let bindings = parse_bindings(&val).ok_or_else(|| invalid_value(&arg, &val))?;
+                    options.key_bindings.extend(bindings);
+                }
+                "--gamepad" => options.gamepad_path = Some(next_value(&mut args, &arg)?.into()),
+                "--input-pipe" => {
+                    options.input_pipe_path = Some(next_value(&mut args, &arg)?.into())
+                }
+                "--gamepad-map" => {
+                    let val = next_value(&mut args, &arg)?;
+                    let map = gamepad::parse_map(&val).ok_or_else(|| invalid_value(&arg, &val))?;
+                    options.gamepad_map.extend(map);
+                }
+                "--fade" => options.fade = true,
+                "--invert" => options.invert = true,
+                "--scanlines" => options.scanlines = true,
+                "--charset" => {
+                    options.charset = match next_value(&mut args, &arg)?.as_str() {
+                        "blocks" => Charset::Blocks,
+                        "ascii" => match options.charset {
+                            Charset::Ascii(on_char) => Charset::Ascii(on_char),
+                            Charset::Blocks => Charset::Ascii('#'),
+                        },
+                        val => return Err(invalid_value(&arg, val)),
+                    };
+                }
+                "--on-char" => {
+                    let val = next_value(&mut args, &arg)?;
+                    let mut chars = val.chars();
+                    options.charset = match (chars.next(), chars.next()) {
+                        (Some(on_char), None) if on_char.is_ascii_graphic() => {
+                            Charset::Ascii(on_char)
+                        }
+                        _ => return Err(invalid_value(&arg, &val)),
+                    };
+                }
+                "--cell-width" => {
+                    options.cell_width = match next_value(&mut args, &arg)?.as_str() {
+                        "1" => 1,
+                        "2" => 2,
+                        val => return Err(invalid_value(&arg, val)),
+                    };
+                }
+                "--width" | "--height" => {
+                    let val = next_value(&mut args, &arg)?;
+                    let size = val
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|size| (16..=256).contains(size))
+                        .ok_or_else(|| invalid_value(&arg, &val))?;
+                    if arg == "--width" {
+                        options.width = size;
+                    } else {
+                        options.height = size;
+                    }
+                }
+                "--scale" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.scale = val
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|scale| (1..=4).contains(scale))
+                        .ok_or_else(|| invalid_value(&arg, &val))?;
+                }
+                "--cls-flash" => {
+                    let val = next_value(&mut args, &arg)?;
+                    let color = parse_color(&val).ok_or_else(|| invalid_value(&arg, &val))?;
+                    options.cls_flash = Some(color);
+                }
+                "--color" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.color = parse_color(&val).ok_or_else(|| invalid_value(&arg, &val))?;
+                }
+                "--no-alt-screen" => options.no_alt_screen = true,
+                "--frame-skip" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.frame_skip = val
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|skip| *skip <= 10)
+                        .ok_or_else(|| invalid_value(&arg, &val))?;
+                }
+                "--start-paused" => options.start_paused = true,
+                "--confirm-quit" => options.confirm_quit = true,
+                "--key-map-hud" => options.key_map_hud = true,
+                "--bell" => options.bell = true,
+                "--mute" => options.mute = true,
+                "--font" => options.font_path = Some(next_value(&mut args, &arg)?.into()),
+                "--persist-flags" => options.persist_flags = true,
+                "--rom-info" => options.rom_info = true,
+                "--title" => options.title = next_value(&mut args, &arg)?,
+                "--attract" => options.attract = true,
+                "--preflight" => options.preflight = true,
+                "--pause-on-exit" => options.pause_on_exit = true,
+                "--opcode-stats" => options.opcode_stats = true,
+                "--draw-rate" => options.draw_rate = true,
+                "--debug" => options.debug = true,
+                "--slow-draw" => {
+                    let val = next_value(&mut args, &arg)?;
+                    let ms = val
+                        .parse::<u64>()
+                        .ok()
+                        .filter(|ms| (1..=1000).contains(ms))
+                        .ok_or_else(|| invalid_value(&arg, &val))?;
+                    options.slow_draw = Some(Duration::from_millis(ms));
+                }
+                "--trace" => options.trace_path = Some(next_value(&mut args, &arg)?.into()),
+                "--trace-format" => {
+                    options.trace_format = match next_value(&mut args, &arg)?.as_str() {
+                        "notes" => TraceFormat::Notes,
+                        "state" => TraceFormat::State,
+                        val => return Err(invalid_value(&arg, val)),
+                    };
+                }
+                "--collision-log" => {
+                    options.collision_log_path = Some(next_value(&mut args, &arg)?.into())
+                }
+                "--timing-log" => {
+                    options.timing_log_path = Some(next_value(&mut args, &arg)?.into())
+                }
+                "--record" => options.record_path = Some(next_value(&mut args, &arg)?.into()),
+                "--replay" => options.replay_path = Some(next_value(&mut args, &arg)?.into()),
+                "--cycles" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.cycles = Some(val.parse().map_err(|_| invalid_value(&arg, &val))?);
+                }
+                "--load-state" => {
+                    options.load_state_path = Some(next_value(&mut args, &arg)?.into())
+                }
+                "--dump-state-on-exit" => {
+                    options.dump_state_path = Some(next_value(&mut args, &arg)?.into())
+                }
+                "--seed" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.seed = Some(val.parse().map_err(|_| invalid_value(&arg, &val))?);
+                }
+                "--frames" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.frames = Some(val.parse().map_err(|_| invalid_value(&arg, &val))?);
+                }
+                "--instructions" => {
+                    let val = next_value(&mut args, &arg)?;
+                    options.bench_instructions =
+                        val.parse().map_err(|_| invalid_value(&arg, &val))?;
+                }
+                "--selftest" => options.selftest = true,
+                "-h" | "--help" => options.show_help = true,
+                _ if !arg.starts_with('-') => positionals.push(arg),
+                _ => return Err(format!("Unknown option \"{}\"", arg)),
+            }
+        }
+
+        options.command = match positionals.as_slice() {
+            [] if options.selftest => Command::SelfTest,
+            [] => Command::Run,
+            [command, rom] if command == "bench" => Command::Bench(rom.into()),
+            [command] if command == "bench" => return Err("Missing ROM for \"bench\"".to_string()),
+            [command, rom, inputs] if command == "diff" => Command::Diff(rom.into(), inputs.into()),
+            [command, ..] if command == "diff" => {
+                return Err("Missing ROM or inputs for \"diff\"".to_string())
+            }
+            [arg, ..] => return Err(format!("Unexpected argument \"{}\"", arg)),
+        };
+
+        // checked once every option is in, since the pause key, --debug and --arrows change which
+        // keys are taken
+        if is_hotkey(options.pause_key, options.debug) {
+            return Err(format!(
+                "Can't use \"{}\" as the pause key, it's already a hotkey",
+                key_name(options.pause_key)
+            ));
+        }
+        if default_key_map(options.arrow_keys).contains_key(&options.pause_key) {
+            return Err(format!(
+                "Can't use \"{}\" as the pause key, it's mapped to a hex key",
+                key_name(options.pause_key)
+            ));
+        }
+        let hotkey = options
+            .key_bindings
+            .iter()
+            .map(|(key, _)| *key)
+            .find(|key| *key == options.pause_key || is_hotkey(*key, options.debug));
+        if let Some(key) = hotkey {
+            return Err(format!(
+                "Can't bind \"{}\", it's already a hotkey",
+                key_name(key)
+            ));
+        }
+
+        Ok(options)
+    }
+
+    // changes options as if they'd been given on the command line, for the settings screen, so they
+    // still apply over per-ROM options
+    pub fn with_flag(&self, flag: &str, on: bool) -> Result<Self, String> {
+        let mut args = self.args_without(flag, false);
+        if on {
+            args.push(flag.to_string());
+        }
+        Self::parse(args.into_iter())
+    }
+
+    pub fn with_value(&self, flag: &str, val: &str) -> Result<Self, String> {
+        let mut args = self.args_without(flag, true);
+        args.extend([flag.to_string(), val.to_string()]);
+        Self::parse(args.into_iter())
+    }
+
+    fn args_without(&self, flag: &str, takes_value: bool) -> Vec<String> {
+        let mut args = vec![];
+        let mut old_args = self.args.iter();
+        while let Some(arg) = old_args.next() {
+            if arg != flag {
+                args.push(arg.clone());
+            } else if takes_value {
+                old_args.next();
+            }
+        }
+        args
+    }
+
+    // applies options under the command-line options, e.g. those saved from the settings screen
+    pub fn with_defaults(&self, defaults: Vec<String>) -> Result<Self, String> {
+        Self::parse(defaults.into_iter().chain(self.args.clone()))
+    }
+
+    // applies options from the ROM's sidecar file, e.g. "pong.ch8.cfg", if it has one, under the
+    // command-line options
+    pub fn for_rom(&self, rom_path: &Path) -> Result<Self, String> {
+        let mut path = OsString::from(rom_path);
+        path.push(SIDECAR_EXTENSION);
+        let path = PathBuf::from(path);
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            // including for ROMs in zip archives, which can't have options files next to them
+            Err(_) if !path.exists() => return Ok(self.clone()),
+            Err(err) => return Err(format!("Failed to read {}: {}", path.display(), err)),
+        };
+
+        let mut args = vec![];
+        for (line_idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, val) = line
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid line {} in {}", line_idx + 1, path.display()))?;
+            let val = val.trim();
+            match val {
+                "true" => args.push(format!("--{}", key.trim())),
+                "false" => (),
+                _ => args.extend([format!("--{}", key.trim()), val.to_string()]),
+            }
+        }
+
+        Self::parse(args.into_iter().chain(self.args.clone()))
+            .map_err(|err| format!("{} (in {} or command line)", err, path.display()))
+    }
+
+    // applies the default quirks and memory size of the variant set with --variant, under the
+    // other options; a guessed variant isn't applied, as a pair of sprite bytes can look like a
+    // SUPER-CHIP or XO-CHIP opcode, and change how a plain CHIP-8 ROM runs
+    pub fn for_variant(&self) -> Result<Self, String> {
+        let defaults: &[&str] = match self.variant {
+            None | Some(Variant::Chip8) => &[],
+            Some(Variant::SuperChip) => &["--jump-vx"],
+            Some(Variant::XoChip) => &["--memory", "65536"],
+        };
+
+        let args = defaults.iter().map(|arg| arg.to_string());
+        Self::parse(args.chain(self.args.clone()))
+    }
+}
+
+// parses bindings like "up=2,enter=5", with hex CHIP-8 keys
+fn parse_bindings(val: &str) -> Option<Vec<(KeyCode, u8)>> {
+    val.split(',')
+        .map(|binding| {
+            let (key, hex_key) = binding.trim().split_once('=')?;
+            let key = parse_key_name(key).filter(|key| !matches!(*key, KeyCode::Esc | CTRL_C))?;
+            let hex_key = u8::from_str_radix(hex_key, 16)
+                .ok()
+                .filter(|val| *val < 0x10)?;
+            Some((key, hex_key))
+        })
+        .collect()
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("Missing value for \"{}\"", flag))
+}
+
+fn invalid_value(flag: &str, val: &str) -> String {
+    format!("Invalid value \"{}\" for \"{}\"", val, flag)
+}
+
+// accepts values with or without a "0x" prefix
+pub fn parse_hex(val: &str) -> Option<u16> {
+    let digits = val
+        .strip_prefix("0x")
+        .or_else(|| val.strip_prefix("0X"))
+        .unwrap_or(val);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+// a color name, e.g. "dark_green", or "#rrggbb"
+fn parse_color(val: &str) -> Option<Color> {
+    if let Some(hex) = val.strip_prefix('#') {
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)?;
+        return Some(Color::Rgb {
+            r: (rgb >> 16) as u8,
+            g: (rgb >> 8) as u8,
+            b: rgb as u8,
+        });
+    }
+
+    Color::try_from(val).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, String> {
+        Options::parse(args.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn values_are_range_checked() {
+        assert_eq!(parse("--speed 60").unwrap().speed, 60);
+        assert!(parse("--speed 59").is_err());
+        assert!(parse("--speed 1000001").is_err());
+        assert!(parse("--speed fast").is_err());
+        assert!(parse("--speed").is_err()); // missing value
+
+        assert_eq!(parse("--memory 8192").unwrap().memory_size, 8192);
+        assert!(parse("--memory 6000").is_err()); // not a power of 2
+        assert!(parse("--memory 2048").is_err());
+        assert!(parse("--memory 131072").is_err());
+
+        assert_eq!(
+            parse("--key-ttl 20").unwrap().key_ttl,
+            Duration::from_millis(20)
+        );
+        assert!(parse("--key-ttl 19").is_err());
+        assert!(parse("--key-ttl 1001").is_err());
+
+        let options = parse("--width 16 --height 256").unwrap();
+        assert_eq!((options.width, options.height), (16, 256));
+        assert!(parse("--width 15").is_err());
+        assert!(parse("--height 257").is_err());
+
+        assert!(parse("--variant gameboy").is_err());
+        assert!(parse("--nonsense").is_err());
+    }
+
+    #[test]
+    fn hex_values_with_or_without_prefix() {
+        assert_eq!(parse_hex("200"), Some(0x200));
+        assert_eq!(parse_hex("0x600"), Some(0x600));
+        assert_eq!(parse_hex("0XFFFF"), Some(0xFFFF));
+        assert_eq!(parse_hex("10000"), None);
+        assert_eq!(parse_hex("0x"), None);
+        assert_eq!(parse_hex("xyz"), None);
+        assert_eq!(parse("--load-addr 0x600").unwrap().load_addr, 0x600);
+    }
+
+    #[test]
+    fn bindings_parsed_by_key_name() {
+        assert_eq!(
+            parse_bindings("up=2, enter=a,k=F"),
+            Some(vec![
+                (KeyCode::Up, 0x2),
+                (KeyCode::Enter, 0xA),
+                (KeyCode::Char('k'), 0xF)
+            ])
+        );
+        assert_eq!(parse_bindings("k=10"), None); // past F
+        assert_eq!(parse_bindings("k"), None);
+        assert_eq!(parse_bindings("nokey=1"), None);
+        assert_eq!(parse_bindings("esc=1"), None);
+        let options = parse("--bind k=2 --bind j=4").unwrap();
+        assert_eq!(options.key_bindings.len(), 2);
+    }
+
+    #[test]
+    fn later_values_replace_earlier_ones() {
+        let options = parse("--speed 900 --jump-vx").unwrap();
+        let options = options.with_value("--speed", "1200").unwrap();
+        assert_eq!(options.speed, 1200);
+        assert!(options.quirks.jump_vx);
+
+        let options = options.with_flag("--jump-vx", false).unwrap();
+        assert!(!options.quirks.jump_vx);
+        assert_eq!(options.speed, 1200);
+
+        // defaults, e.g. saved settings, are under the command line
+        let options = parse("--speed 900").unwrap();
+        let options = options
+            .with_defaults(vec!["--speed".to_string(), "800".to_string()])
+            .unwrap();
+        assert_eq!(options.speed, 900);
+        let options = Options::new()
+            .with_defaults(vec!["--speed".to_string(), "800".to_string()])
+            .unwrap();
+        assert_eq!(options.speed, 800);
+    }
+
+    #[test]
+    fn command_line_over_rom_options_over_variant() {
+        let dir = env::temp_dir().join(format!("rust-chip-8-options-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rom_path = dir.join("game.ch8");
+        let mut sidecar_path = OsString::from(&rom_path);
+        sidecar_path.push(SIDECAR_EXTENSION);
+        fs::write(
+            &sidecar_path,
+            "# for this game\nvariant = xochip\nmemory = 8192\n\nspeed = 1000\n\
+             display-wait = true\njump-vx = false\ntitle = My Game\n",
+        )
+        .unwrap();
+
+        let options = |args: &str| {
+            parse(args)
+                .and_then(|options| options.for_rom(&rom_path))
+                .and_then(|options| options.for_variant())
+        };
+        let from_file = options("").unwrap();
+        assert_eq!(from_file.memory_size, 8192); // over xochip's 65536
+        assert_eq!(from_file.speed, 1000);
+        assert!(from_file.quirks.display_wait);
+        assert!(!from_file.quirks.jump_vx);
+        assert_eq!(from_file.title, "My Game");
+
+        let from_command_line = options("--memory 16384 --speed 500").unwrap();
+        assert_eq!(from_command_line.memory_size, 16384);
+        assert_eq!(from_command_line.speed, 500);
+
+        let variant_only = parse("--variant xochip")
+            .and_then(|options| options.for_variant())
+            .unwrap();
+        assert_eq!(variant_only.memory_size, 65536);
+
+        fs::write(&sidecar_path, "speed = 1000\nnot an option line\n").unwrap();
+        assert!(options("").err().unwrap().contains("Invalid line 2"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn bindings_and_pause_key_cant_clash_with_taken_keys() {
+        assert!(parse("--bind k=5,up=2").is_ok());
+        assert!(parse("--bind m=5").is_err()); // mute hotkey
+        assert!(parse("--bind space=5").is_err()); // the default pause key
+        assert!(parse("--pause-key tab --bind space=5").is_ok());
+        assert!(parse("--pause-key tab --bind tab=5").is_err());
+        assert!(parse("--bind f5=5").is_ok());
+        assert!(parse("--debug --bind f5=5").is_err()); // debug keys
+
+        assert!(parse("--pause-key w").is_err()); // hex 5
+        assert!(parse("--pause-key o").is_err()); // quirks hotkey
+        assert!(parse("--pause-key up").is_ok());
+        assert!(parse("--arrows --pause-key up").is_err()); // hex 2 with --arrows
+        assert!(parse("--pause-key f2").is_ok());
+        assert!(parse("--debug --pause-key f2").is_err());
+    }
+}
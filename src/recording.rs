@@ -0,0 +1,81 @@
+use crate::keyboard::{key_name, parse_key_name};
+
+use crossterm::event::KeyCode;
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+// Input recordings are plain text, one key press per line: "<frame> <key>", e.g. "120 w"
+
+pub struct InputRecording {
+    path: PathBuf,
+    events: Vec<(u64, KeyCode)>,
+}
+
+impl InputRecording {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            events: vec![],
+        }
+    }
+
+    pub fn push(&mut self, frame: u64, key: KeyCode) {
+        self.events.push((frame, key));
+    }
+
+    pub fn save(&self) -> Result<(), io::Error> {
+        let mut file = io::BufWriter::new(fs::File::create(&self.path)?);
+        for (frame, key) in &self.events {
+            writeln!(file, "{} {}", frame, key_name(*key))?;
+        }
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+pub struct InputPlayback {
+    events: VecDeque<(u64, KeyCode)>,
+}
+
+impl InputPlayback {
+    pub fn load(path: &Path) -> Result<Self, io::Error> {
+        let contents = fs::read_to_string(path)?;
+
+        let mut events = VecDeque::new();
+        for (line_idx, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let event = line
+                .split_once(' ')
+                .and_then(|(frame, key)| Some((frame.parse::<u64>().ok()?, parse_key_name(key)?)));
+            match event {
+                Some(event) => events.push_back(event),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Invalid input recording entry on line {}", line_idx + 1),
+                    ))
+                }
+            }
+        }
+
+        Ok(Self { events })
+    }
+
+    // returns the next recorded key press due on or before the given frame
+    pub fn next_event(&mut self, frame: u64) -> Option<KeyCode> {
+        match self.events.front() {
+            Some((event_frame, _)) if *event_frame <= frame => {
+                self.events.pop_front().map(|(_, key)| key)
+            }
+            _ => None,
+        }
+    }
+}
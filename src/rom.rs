@@ -0,0 +1,219 @@
+use crate::asm;
+use crate::cpu;
+use crate::error::Result;
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::{Read, Seek},
+    path::Path,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Variant {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Variant::Chip8 => write!(f, "CHIP-8"),
+            Variant::SuperChip => write!(f, "SUPER-CHIP"),
+            Variant::XoChip => write!(f, "XO-CHIP"),
+        }
+    }
+}
+
+// Reads a ROM file, or a ROM in a zip archive, by a path through the archive, e.g.
+// "roms/games.zip/pong/pong.ch8"; assembly source (.asm) is assembled, to load at load_addr
+pub fn read(path: &Path, load_addr: u16) -> Result<Vec<u8>> {
+    let bytes = read_file(path)?;
+    if path.extension() == Some("asm".as_ref()) {
+        return asm::assemble(&String::from_utf8_lossy(&bytes), load_addr);
+    }
+
+    Ok(bytes)
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    for archive_path in path.ancestors().skip(1) {
+        if archive_path.extension() == Some("zip".as_ref()) && archive_path.is_file() {
+            // zip entry names always use '/'
+            let entry_name = path
+                .strip_prefix(archive_path)
+                .unwrap_or(path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            return read_zip_entry(fs::File::open(archive_path)?, &entry_name);
+        }
+    }
+
+    Ok(fs::read(path)?)
+}
+
+// Lists the files in a zip archive, by their paths within it, e.g. "pong/pong.ch8", sorted
+pub fn zip_entries(archive: impl Read + Seek) -> Result<Vec<String>> {
+    let archive = zip::ZipArchive::new(archive)?;
+    let mut names = archive
+        .file_names()
+        .filter(|name| !name.ends_with('/')) // directories
+        .map(String::from)
+        .collect::<Vec<_>>();
+    names.sort();
+
+    Ok(names)
+}
+
+fn read_zip_entry(archive: impl Read + Seek, name: &str) -> Result<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(archive)?;
+    let mut bytes = vec![];
+    archive.by_name(name)?.read_to_end(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+// CRC-32 (IEEE), as used by zip, PNG, etc., so hashes can be compared against other tools
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFFFFFF_u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB88320 & mask);
+        }
+    }
+    !crc
+}
+
+// Guesses the variant a ROM targets from the opcodes it contains. Only instructions at even
+// offsets are checked, and data can look like code, so this is a heuristic, shown in the ROM info
+// but not applied.
+pub fn guess_variant(bytes: &[u8]) -> Variant {
+    bytes
+        .chunks_exact(2)
+        .map(|pair| opcode_variant((pair[0] as u16) << 8 | pair[1] as u16))
+        .max()
+        .unwrap_or(Variant::Chip8)
+}
+
+// Finds opcodes the emulator doesn't implement, by walking the ROM 2 bytes at a time. Jumps aren't
+// followed, so data can be flagged too. Zeroed bytes are skipped, as they're almost always padding.
+// Returns (opcode, first address, count), by opcode. It runs before the ROM is loaded, so stops at
+// the end of memory, where a ROM too big for it will fail to load anyway.
+pub fn unsupported_opcodes(
+    bytes: &[u8],
+    load_addr: u16,
+    memory_size: usize,
+) -> Vec<(u16, u16, usize)> {
+    let mut found = BTreeMap::<u16, (u16, usize)>::new();
+    for (idx, pair) in bytes.chunks_exact(2).enumerate() {
+        let addr = load_addr as usize + idx * 2;
+        if addr + 2 > memory_size {
+            break;
+        }
+        let opcode = (pair[0] as u16) << 8 | pair[1] as u16;
+        if opcode == 0x0000 || cpu::is_implemented(opcode) {
+            continue;
+        }
+
+        found.entry(opcode).or_insert((addr as u16, 0)).1 += 1;
+    }
+
+    found
+        .into_iter()
+        .map(|(opcode, (addr, count))| (opcode, addr, count))
+        .collect()
+}
+
+fn opcode_variant(opcode: u16) -> Variant {
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00FB..=0x00FF => Variant::SuperChip, // scroll, exit, lo-res, hi-res
+            _ if opcode & 0xFFF0 == 0x00C0 => Variant::SuperChip, // scroll down
+            _ if opcode & 0xFFF0 == 0x00D0 => Variant::XoChip, // scroll up
+            _ => Variant::Chip8,
+        },
+        0x5000 => match opcode & 0xF {
+            0x2 | 0x3 => Variant::XoChip, // save/load register range
+            _ => Variant::Chip8,
+        },
+        0xF000 => match opcode & 0xFF {
+            0x00 if opcode == 0xF000 => Variant::XoChip, // long load
+            0x01 | 0x02 | 0x3A => Variant::XoChip,       // plane, audio, pitch
+            0x30 | 0x75 | 0x85 => Variant::SuperChip,    // big font, flag registers
+            _ => Variant::Chip8,
+        },
+        _ => Variant::Chip8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zip_entries_listed_and_read() {
+        use std::io::{Cursor, Write};
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default();
+        writer.add_directory("pong/", options).unwrap();
+        writer.start_file("pong/pong.ch8", options).unwrap();
+        writer.write_all(&[0x12, 0x00]).unwrap();
+        writer.start_file("maze.ch8", options).unwrap();
+        writer.write_all(&[0x60, 0x0A]).unwrap();
+        let archive = writer.finish().unwrap();
+
+        assert_eq!(
+            zip_entries(archive.clone()).unwrap(),
+            vec!["maze.ch8", "pong/pong.ch8"]
+        );
+        assert_eq!(
+            read_zip_entry(archive.clone(), "pong/pong.ch8").unwrap(),
+            vec![0x12, 0x00]
+        );
+        assert!(read_zip_entry(archive, "missing.ch8").is_err());
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn guess_variant_from_opcodes() {
+        assert!(guess_variant(&[0x60, 0x0A, 0x12, 0x00]) == Variant::Chip8);
+        assert!(guess_variant(&[0x00, 0xFF, 0x12, 0x00]) == Variant::SuperChip);
+        assert!(guess_variant(&[0x00, 0xFF, 0xF0, 0x00]) == Variant::XoChip);
+    }
+
+    #[test]
+    fn unsupported_opcodes_found() {
+        let bytes = [0x60, 0x0A, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0xF0, 0x02];
+        assert_eq!(
+            unsupported_opcodes(&bytes, 0x200, 4096),
+            vec![(0x00FF, 0x202, 2), (0xF002, 0x208, 1)]
+        );
+    }
+
+    #[test]
+    fn unsupported_opcodes_stop_at_end_of_memory() {
+        let mut bytes = vec![0x00; 4096];
+        bytes[0x0FE] = 0xF0; // the last opcode that fits, at 0xFFE
+        bytes[0x0FF] = 0x02;
+        bytes[0x100] = 0xF0; // past the end
+        bytes[0x101] = 0x03;
+        assert_eq!(
+            unsupported_opcodes(&bytes, 0xF00, 4096),
+            vec![(0xF002, 0xFFE, 1)]
+        );
+        // and past the end of a 16-bit address space
+        assert!(unsupported_opcodes(&vec![0xF0; 0x10000], 0xF00, 65536)
+            .iter()
+            .all(|(_, addr, _)| *addr >= 0xF00));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::cpu::Cpu;
+use crate::display::Display;
+use crate::error::{Error, Result};
+use crate::keyboard::Keyboard;
+use crate::options::Options;
+
+const TESTS: u8 = 3;
+// runs each test, and draws its number with a check mark or a cross; VD ends up as the number of
+// tests failed, and VE as the number run
+const PROGRAM: [u8; 162] = [
+    0x6D, 0x00, // VD = 0, the failure count
+    // 1: arithmetic, and its carry and borrow flags
+    0x6C, 0x00, // VC = 0, set by FAIL
+    0x6A, 0x05, // VA = 5
+    0x6B, 0x03, // VB = 3
+    0x8A, 0xB4, // VA += VB, no carry
+    0x3A, 0x08, // skip if VA = 8
+    0x22, 0x78, // call FAIL
+    0x3F, 0x00, // skip if VF = 0
+    0x22, 0x78, // call FAIL
+    0x6A, 0xFF, // VA = FF
+    0x6B, 0x02, // VB = 2
+    0x8A, 0xB4, // VA += VB, carry
+    0x3A, 0x01, // skip if VA = 1
+    0x22, 0x78, // call FAIL
+    0x3F, 0x01, // skip if VF = 1
+    0x22, 0x78, // call FAIL
+    0x6A, 0x05, // VA = 5
+    0x8A, 0xB5, // VA -= VB, no borrow
+    0x3A, 0x03, // skip if VA = 3
+    0x22, 0x78, // call FAIL
+    0x3F, 0x01, // skip if VF = 1
+    0x22, 0x78, // call FAIL
+    0x6E, 0x01, // VE = test 1
+    0x22, 0x7C, // call REPORT
+    // 2: drawing, and collisions
+    0x6C, 0x00, // VC = 0
+    0x60, 0x38, // V0 = x 56
+    0x61, 0x00, // V1 = y 0
+    0x62, 0x08, // V2 = 8
+    0xF2, 0x29, // I = sprite for V2
+    0xD0, 0x15, // draw
+    0x3F, 0x00, // skip if VF = 0, nothing erased
+    0x22, 0x78, // call FAIL
+    0xD0, 0x15, // draw again, erasing it
+    0x3F, 0x01, // skip if VF = 1, collision
+    0x22, 0x78, // call FAIL
+    0x6E, 0x02, // VE = test 2
+    0x22, 0x7C, // call REPORT
+    // 3: the delay timer counts down, within about 65000 reads
+    0x6C, 0x00, // VC = 0
+    0x63, 0x05, // V3 = 5
+    0xF3, 0x15, // DT = V3
+    0xF7, 0x07, // V7 = DT
+    0x47, 0x00, // skip if V7 != 0
+    0x22, 0x78, // call FAIL
+    0x64, 0x00, // V4 = 0, wait count low byte
+    0x65, 0x00, // V5 = 0, high byte
+    0x66, 0x01, // V6 = 1
+    0xF3, 0x07, // V3 = DT
+    0x43, 0x00, // skip if V3 != 0
+    0x12, 0x72, // jump to DONE, timer ran down
+    0x84, 0x64, // V4 += V6, VF = carry
+    0x4F, 0x01, // skip if VF != 1
+    0x75, 0x01, // V5 += 1
+    0x45, 0xFF, // skip if V5 != FF
+    0x22, 0x78, // call FAIL, timer stuck
+    0x45, 0xFF, // skip if V5 != FF
+    0x12, 0x72, // jump to DONE
+    0x12, 0x5C, // jump to WAIT
+    0x6E, 0x03, // VE = test 3
+    0x22, 0x7C, // call REPORT
+    0x12, 0x76, // jump to END, wait for ESC
+    // FAIL: marks the current test failed
+    0x6C, 0x01, // VC = 1
+    0x00, 0xEE, // return
+    // REPORT: draws test VE's number, then a check mark or a cross, and counts failures
+    0x81, 0xE0, // V1 = VE
+    0x81, 0x0E, // V1 <<= 1
+    0x81, 0x0E, // V1 <<= 1
+    0x81, 0x0E, // V1 <<= 1, y = test number * 8
+    0x60, 0x08, // V0 = x 8
+    0xFE, 0x29, // I = sprite for VE
+    0xD0, 0x15, // draw the test number
+    0x60, 0x10, // V0 = x 16
+    0xA2, 0x98, // I = check mark
+    0x3C, 0x00, // skip if VC = 0, passed
+    0xA2, 0x9D, // I = cross
+    0xD0, 0x15, // draw the result
+    0x8D, 0xC4, // VD += VC
+    0x00, 0xEE, // return
+    0x01, 0x02, 0x84, 0x48, 0x30, // check mark
+    0x88, 0x50, 0x20, 0x50, 0x88, // cross
+];
+
+// runs the built-in diagnostic program until the user leaves it, then reports its results
+pub fn run(options: &Options) -> Result<()> {
+    let mut options = options.clone();
+    options.load_addr = 0x200; // the program jumps to fixed addresses
+    options.cycles = None;
+    options.frames = None;
+    options.input_advance = false; // would freeze it between key presses
+    options.debug = false;
+
+    let keyboard = Keyboard::new(options.key_ttl, options.arrow_keys, options.pause_key);
+    let mut cpu = Cpu::new(Display::new(&options), keyboard, &options);
+    cpu.run_program(&PROGRAM)?;
+
+    let state = cpu.state();
+    let (failed, run) = (state.v[0xD], state.v[0xE]);
+    if failed > 0 {
+        return Err(Error::SelfTestFailed { failed, run });
+    }
+    if run < TESTS {
+        println!(
+            "Self-test stopped after {} of {} tests, all passed",
+            run, TESTS
+        );
+    } else {
+        println!("Self-test passed");
+    }
+
+    Ok(())
+}
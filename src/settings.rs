@@ -0,0 +1,98 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+// Settings remembered between runs, stored as "key=value" lines in the OS config dir
+
+pub struct Settings {
+    pub last_rom: Option<String>,
+    pub favorites: Vec<String>, // ROM file names, pinned to the top of the menu
+    pub speed: Option<u32>,     // last chosen on the settings screen
+    pub color: Option<String>,  // by name, as for --color
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        let mut settings = Self {
+            last_rom: None,
+            favorites: vec![],
+            speed: None,
+            color: None,
+        };
+
+        let contents = match settings_path().map(fs::read_to_string) {
+            Some(Ok(contents)) => contents,
+            _ => return settings, // nothing saved yet, or no config dir
+        };
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("last_rom", val)) => settings.last_rom = Some(val.to_string()),
+                Some(("favorite", val)) => settings.favorites.push(val.to_string()),
+                Some(("speed", val)) => settings.speed = val.parse().ok(),
+                Some(("color", val)) => settings.color = Some(val.to_string()),
+                _ => (),
+            }
+        }
+
+        settings
+    }
+
+    pub fn save(&self) -> Result<(), io::Error> {
+        let path = match settings_path() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut lines = vec![];
+        if let Some(last_rom) = &self.last_rom {
+            lines.push(format!("last_rom={}", last_rom));
+        }
+        for favorite in &self.favorites {
+            lines.push(format!("favorite={}", favorite));
+        }
+        if let Some(speed) = self.speed {
+            lines.push(format!("speed={}", speed));
+        }
+        if let Some(color) = &self.color {
+            lines.push(format!("color={}", color));
+        }
+
+        fs::write(path, lines.join("\n"))
+    }
+
+    // the saved choices as command-line options, to apply under the actual command line
+    pub fn option_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if let Some(speed) = self.speed {
+            args.extend(["--speed".to_string(), speed.to_string()]);
+        }
+        if let Some(color) = &self.color {
+            args.extend(["--color".to_string(), color.clone()]);
+        }
+        args
+    }
+}
+
+fn settings_path() -> Option<PathBuf> {
+    app_config_path("settings")
+}
+
+// path of a file in this program's config dir
+pub fn app_config_path(name: impl AsRef<Path>) -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("rust-chip-8").join(name))
+}
+
+fn config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(PathBuf::from);
+    }
+
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+}
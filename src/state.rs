@@ -0,0 +1,374 @@
+use crate::error::{Error, Result};
+
+use std::{fs, iter::Peekable, path::Path, str::Chars};
+
+// Snapshot of the machine, e.g. for test scripts to assert on after a run, or to start a ROM from
+
+// the version of the JSON format; raise it when fields change meaning or are added, and have
+// from_json migrate older versions, or reject them if that isn't possible
+pub const STATE_VERSION: u64 = 1;
+
+pub struct CpuState {
+    pub pc: u16,
+    pub i: u16,
+    pub v: [u8; 0x10],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub stack: Vec<u16>,
+    pub cols: usize,          // blocks in each row of the framebuffer
+    pub framebuffer: Vec<u8>, // 1 for each lit block, row by row
+}
+
+impl CpuState {
+    // framebuffer rows are strings of 0s and 1s, so they're readable in the file
+    pub fn to_json(&self) -> String {
+        let list = |vals: Vec<String>| vals.join(", ");
+        let rows = self
+            .framebuffer
+            .chunks(self.cols)
+            .map(|row| {
+                let row = row
+                    .iter()
+                    .map(|block| block.to_string())
+                    .collect::<String>();
+                format!("    \"{}\"", row)
+            })
+            .collect::<Vec<_>>();
+
+        [
+            "{".to_string(),
+            format!("  \"version\": {},", STATE_VERSION),
+            format!("  \"pc\": {},", self.pc),
+            format!("  \"i\": {},", self.i),
+            format!(
+                "  \"v\": [{}],",
+                list(self.v.iter().map(|val| val.to_string()).collect())
+            ),
+            format!("  \"delay_timer\": {},", self.delay_timer),
+            format!("  \"sound_timer\": {},", self.sound_timer),
+            format!(
+                "  \"stack\": [{}],",
+                list(self.stack.iter().map(|addr| addr.to_string()).collect())
+            ),
+            "  \"framebuffer\": [".to_string(),
+            rows.join(",\n"),
+            "  ]".to_string(),
+            "}".to_string(),
+        ]
+        .join("\n")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        Ok(fs::write(path, self.to_json() + "\n")?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    // newer versions than this build knows are rejected, rather than guessed at
+    pub fn from_json(json: &str) -> Result<Self> {
+        let fields = match Parser::new(json).parse() {
+            Some(Json::Object(fields)) => fields,
+            _ => return Err(Error::State("not a JSON object".to_string())),
+        };
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, val)| val)
+                .ok_or_else(|| Error::State(format!("missing \"{}\"", name)))
+        };
+
+        let version: u64 = number(field("version")?, "version")?;
+        if version == 0 || version > STATE_VERSION {
+            return Err(Error::State(format!(
+                "version {} isn't supported, this build reads up to version {}",
+                version, STATE_VERSION
+            )));
+        }
+
+        let v = numbers(field("v")?, "v")?;
+        // the size is the display's when saved, and is checked against it on loading
+        let rows = match field("framebuffer")? {
+            Json::Array(rows) => rows
+                .iter()
+                .map(|row| match row {
+                    Json::String(row) => row
+                        .chars()
+                        .map(|block| match block {
+                            '0' => Some(0),
+                            '1' => Some(1),
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<u8>>>(),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>(),
+            _ => None,
+        }
+        .filter(|rows| {
+            !rows.is_empty()
+                && !rows[0].is_empty()
+                && rows.iter().all(|row| row.len() == rows[0].len())
+        })
+        .ok_or_else(|| {
+            Error::State("\"framebuffer\" must be rows of 0s and 1s, all as long".to_string())
+        })?;
+
+        Ok(Self {
+            pc: number(field("pc")?, "pc")?,
+            i: number(field("i")?, "i")?,
+            v: v.try_into()
+                .map_err(|_| Error::State("\"v\" must have 16 registers".to_string()))?,
+            delay_timer: number(field("delay_timer")?, "delay_timer")?,
+            sound_timer: number(field("sound_timer")?, "sound_timer")?,
+            stack: numbers(field("stack")?, "stack")?,
+            cols: rows[0].len(),
+            framebuffer: rows.concat(),
+        })
+    }
+}
+
+// just enough JSON for the state files: objects, arrays, strings without escapes, and whole numbers
+enum Json {
+    Number(u64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(json: &'a str) -> Self {
+        Self {
+            chars: json.chars().peekable(),
+        }
+    }
+
+    // the value, if it's the whole input
+    fn parse(&mut self) -> Option<Json> {
+        let val = self.value()?;
+        self.skip_whitespace();
+        match self.chars.peek() {
+            None => Some(val),
+            Some(_) => None,
+        }
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.skip_whitespace();
+        match *self.chars.peek()? {
+            '{' => {
+                let mut fields = vec![];
+                self.list('}', |parser| {
+                    parser.skip_whitespace();
+                    let key = parser.string()?;
+                    parser.skip_whitespace();
+                    parser.expect(':')?;
+                    fields.push((key, parser.value()?));
+                    Some(())
+                })?;
+                Some(Json::Object(fields))
+            }
+            '[' => {
+                let mut vals = vec![];
+                self.list(']', |parser| {
+                    vals.push(parser.value()?);
+                    Some(())
+                })?;
+                Some(Json::Array(vals))
+            }
+            '"' => self.string().map(Json::String),
+            c if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(digit) = self.chars.next_if(char::is_ascii_digit) {
+                    digits.push(digit);
+                }
+                digits.parse().ok().map(Json::Number)
+            }
+            _ => None,
+        }
+    }
+
+    // items separated by commas, from the opening bracket to the closing one
+    fn list(&mut self, close: char, mut item: impl FnMut(&mut Self) -> Option<()>) -> Option<()> {
+        self.chars.next();
+        self.skip_whitespace();
+        if self.chars.next_if_eq(&close).is_some() {
+            return Some(());
+        }
+        loop {
+            item(self)?;
+            self.skip_whitespace();
+            match self.chars.next()? {
+                ',' => continue,
+                c if c == close => return Some(()),
+                _ => return None,
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+        loop {
+            match self.chars.next()? {
+                '"' => return Some(string),
+                '\\' => return None,
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Option<()> {
+        self.chars.next_if_eq(&c).map(|_| ())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+}
+
+fn number<T: TryFrom<u64>>(val: &Json, name: &str) -> Result<T> {
+    match val {
+        Json::Number(num) => T::try_from(*num).ok(),
+        _ => None,
+    }
+    .ok_or_else(|| Error::State(format!("\"{}\" is out of range", name)))
+}
+
+fn numbers<T: TryFrom<u64>>(val: &Json, name: &str) -> Result<Vec<T>> {
+    match val {
+        Json::Array(vals) => vals.iter().map(|val| number(val, name)).collect(),
+        _ => Err(Error::State(format!("\"{}\" must be a list", name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::{COLS, ROWS};
+
+    fn test_state() -> CpuState {
+        let mut framebuffer = vec![0; COLS * ROWS];
+        framebuffer[COLS + 3] = 1;
+        CpuState {
+            pc: 0x2A4,
+            i: 0x300,
+            v: [7; 0x10],
+            delay_timer: 60,
+            sound_timer: 0,
+            stack: vec![0x202, 0x310],
+            cols: COLS,
+            framebuffer,
+        }
+    }
+
+    #[test]
+    fn loads_what_it_saves() {
+        let state = test_state();
+        let loaded = CpuState::from_json(&state.to_json()).unwrap();
+        assert_eq!(loaded.pc, state.pc);
+        assert_eq!(loaded.i, state.i);
+        assert_eq!(loaded.v, state.v);
+        assert_eq!(loaded.delay_timer, state.delay_timer);
+        assert_eq!(loaded.stack, state.stack);
+        assert_eq!(loaded.cols, state.cols);
+        assert_eq!(loaded.framebuffer, state.framebuffer);
+    }
+
+    #[test]
+    fn loads_what_it_saves_at_other_sizes() {
+        let state = CpuState {
+            stack: vec![],
+            cols: 17,
+            framebuffer: (0..17 * 5).map(|idx| (idx % 2) as u8).collect(),
+            ..test_state()
+        };
+        let loaded = CpuState::from_json(&state.to_json()).unwrap();
+        assert!(loaded.stack.is_empty());
+        assert_eq!(loaded.sound_timer, state.sound_timer);
+        assert_eq!(loaded.cols, 17);
+        assert_eq!(loaded.framebuffer, state.framebuffer);
+    }
+
+    #[test]
+    fn rejects_malformed_json() {
+        let json = test_state().to_json();
+        let error = |json: &str| CpuState::from_json(json).err().unwrap().to_string();
+        let not_object = "Invalid save state: not a JSON object";
+
+        assert_eq!(error(""), not_object);
+        assert_eq!(error("[]"), not_object);
+        assert_eq!(error(&json[..json.len() - 1]), not_object); // unclosed
+        assert_eq!(error(&format!("{} {{}}", json)), not_object); // trailing data
+        assert_eq!(
+            error(&json.replace("\"pc\": 676,", "\"pc\": 676,,")),
+            not_object
+        );
+        assert_eq!(error(&json.replace("  ]\n}", "  ],\n}")), not_object); // trailing comma
+        assert_eq!(error(&json.replace("\"pc\"", "\"p\\u0063\"")), not_object); // escapes
+        assert_eq!(error(&json.replace("676", "-676")), not_object);
+        assert_eq!(error(&json.replace("676", "6.76")), not_object);
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        let json = test_state().to_json();
+        let error = |json: &str| CpuState::from_json(json).err().unwrap().to_string();
+
+        assert_eq!(
+            error(&json.replace("  \"pc\": 676,\n", "")),
+            "Invalid save state: missing \"pc\""
+        );
+        assert_eq!(
+            error(&json.replace("\"pc\": 676", "\"pc\": 65536")),
+            "Invalid save state: \"pc\" is out of range"
+        );
+        assert_eq!(
+            error(&json.replace("\"delay_timer\": 60", "\"delay_timer\": \"60\"")),
+            "Invalid save state: \"delay_timer\" is out of range"
+        );
+        assert_eq!(
+            error(&json.replace("\"v\": [7, ", "\"v\": [")),
+            "Invalid save state: \"v\" must have 16 registers"
+        );
+        assert_eq!(
+            error(&json.replace("\"stack\": [514, 784]", "\"stack\": 514")),
+            "Invalid save state: \"stack\" must be a list"
+        );
+        let framebuffer_error =
+            "Invalid save state: \"framebuffer\" must be rows of 0s and 1s, all as long";
+        assert_eq!(
+            error(&json.replacen("0000\"", "000\"", 1)),
+            framebuffer_error
+        ); // ragged
+        assert_eq!(error(&json.replacen("0001", "0002", 1)), framebuffer_error);
+        let no_rows = json.split("  \"framebuffer\"").next().unwrap().to_string();
+        assert_eq!(
+            error(&(no_rows + "  \"framebuffer\": []\n}")),
+            framebuffer_error
+        );
+    }
+
+    #[test]
+    fn checks_version() {
+        let json = test_state().to_json();
+
+        let unversioned = json.replace("  \"version\": 1,\n", "");
+        assert_eq!(
+            CpuState::from_json(&unversioned).err().unwrap().to_string(),
+            "Invalid save state: missing \"version\""
+        );
+
+        let newer = json.replace("\"version\": 1", "\"version\": 2");
+        assert_eq!(
+            CpuState::from_json(&newer).err().unwrap().to_string(),
+            "Invalid save state: version 2 isn't supported, this build reads up to version 1"
+        );
+    }
+}
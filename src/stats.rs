@@ -0,0 +1,109 @@
+use std::collections::BTreeMap;
+
+const BAR_WIDTH: u64 = 40;
+const FRAMES_PER_SECOND: u32 = 60;
+
+// Tallies executed instructions, grouped by opcode pattern, e.g. all "8xy4" opcodes together
+pub struct OpcodeStats {
+    counts: BTreeMap<u16, u64>, // by opcode group
+}
+
+impl OpcodeStats {
+    pub fn new() -> Self {
+        Self {
+            counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn record(&mut self, opcode: u16) {
+        *self.counts.entry(opcode_group(opcode)).or_insert(0) += 1;
+    }
+
+    // prints a histogram to stderr, most executed first, so it doesn't mix with the display
+    pub fn print(&self) {
+        let total: u64 = self.counts.values().sum();
+        let max = self.counts.values().copied().max().unwrap_or(0);
+
+        let mut counts = self.counts.iter().collect::<Vec<_>>();
+        counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+        eprintln!("Executed opcodes ({} total):", total);
+        for (group, count) in counts {
+            eprintln!(
+                "  {}  {:>10}  {:>5.1}%  {}",
+                group_pattern(*group),
+                count,
+                *count as f64 * 100.0 / total as f64,
+                "#".repeat(((count * BAR_WIDTH + max - 1) / max) as usize),
+            );
+        }
+    }
+}
+
+// Counts DRW and CLS per second of emulated time, i.e. 60 frames, to show how often a ROM redraws,
+// e.g. flickering by erasing and redrawing sprites every frame
+pub struct DrawRate {
+    draws: u32,
+    clears: u32,
+    frames: u32,
+    last_second: Option<(u32, u32)>, // (draws, clears)
+}
+
+impl DrawRate {
+    pub fn new() -> Self {
+        Self {
+            draws: 0,
+            clears: 0,
+            frames: 0,
+            last_second: None,
+        }
+    }
+
+    pub fn record(&mut self, opcode: u16) {
+        if opcode & 0xF000 == 0xD000 {
+            self.draws += 1;
+        } else if opcode == 0x00E0 {
+            self.clears += 1;
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.frames += 1;
+        if self.frames == FRAMES_PER_SECOND {
+            *self = Self {
+                last_second: Some((self.draws, self.clears)),
+                ..Self::new()
+            };
+        }
+    }
+
+    // e.g. "DRW 120/s  CLS 60/s", once a second has run
+    pub fn label(&self) -> Option<String> {
+        self.last_second
+            .map(|(draws, clears)| format!("DRW {}/s  CLS {}/s", draws, clears))
+    }
+}
+
+// masks out the operands, leaving the bits that select the instruction
+fn opcode_group(opcode: u16) -> u16 {
+    match opcode & 0xF000 {
+        0x0000 if opcode & 0xFF00 == 0 => opcode,
+        0x0000 => 0x0100, // SYS, with any address
+        0x5000 | 0x8000 | 0x9000 => opcode & 0xF00F,
+        0xE000 | 0xF000 => opcode & 0xF0FF,
+        _ => opcode & 0xF000,
+    }
+}
+
+fn group_pattern(group: u16) -> String {
+    let hex = format!("{:04X}", group);
+    match group & 0xF000 {
+        0x0000 if group == 0x0100 => "0nnn".to_string(),
+        0x0000 => hex,
+        0x1000 | 0x2000 | 0xA000 | 0xB000 => format!("{}nnn", &hex[..1]),
+        0x3000 | 0x4000 | 0x6000 | 0x7000 | 0xC000 => format!("{}xkk", &hex[..1]),
+        0xD000 => "Dxyn".to_string(),
+        0xE000 | 0xF000 => format!("{}x{}", &hex[..1], &hex[2..]),
+        _ => format!("{}xy{}", &hex[..1], &hex[3..]),
+    }
+}
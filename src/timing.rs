@@ -0,0 +1,45 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+// CSV log of where each frame's time went, to graph frame-time jitter, one row per frame, in
+// microseconds: executing instructions (and debug keys), updating and rendering the display, and
+// sleeping until the next frame is due
+
+pub struct TimingLog {
+    writer: io::BufWriter<fs::File>,
+}
+
+pub struct FrameTiming {
+    pub execute: Duration,
+    pub render: Duration,
+    pub sleep: Duration,
+    pub total: Duration,
+}
+
+impl TimingLog {
+    pub fn create(path: &Path) -> Result<Self, io::Error> {
+        let mut writer = io::BufWriter::new(fs::File::create(path)?);
+        writeln!(writer, "frame,execute_us,render_us,sleep_us,total_us")?;
+        Ok(Self { writer })
+    }
+
+    pub fn log(&mut self, frame: u64, timing: &FrameTiming) -> Result<(), io::Error> {
+        writeln!(
+            self.writer,
+            "{},{},{},{},{}",
+            frame,
+            timing.execute.as_micros(),
+            timing.render.as_micros(),
+            timing.sleep.as_micros(),
+            timing.total.as_micros(),
+        )
+    }
+
+    pub fn flush(&mut self) -> Result<(), io::Error> {
+        self.writer.flush()
+    }
+}
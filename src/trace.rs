@@ -0,0 +1,110 @@
+use crate::options::TraceFormat;
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+// Trace log of executed instructions, one per line: "<address> <opcode>", e.g. "0208 D015",
+// followed by notes for some instructions, e.g. "erased=3" for a DRW that erased 3 pixels; or in
+// the state format, the registers before each instruction, e.g.
+// "PC:0208 OP:D015 V0:00 ... VF:00 I:0300 SP:0 DT:00 ST:00", in fixed columns, so two runs can be
+// diffed to find the first instruction where they differ
+
+// the registers, as they were before an instruction
+pub struct Registers {
+    pub v: [u8; 0x10],
+    pub i: u16,
+    pub sp: usize, // subroutines on the stack
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+pub struct Trace {
+    writer: io::BufWriter<fs::File>,
+    format: TraceFormat,
+}
+
+impl Trace {
+    pub fn create(path: &Path, format: TraceFormat) -> Result<Self, io::Error> {
+        Ok(Self {
+            writer: io::BufWriter::new(fs::File::create(path)?),
+            format,
+        })
+    }
+
+    pub fn log(
+        &mut self,
+        addr: u16,
+        opcode: u16,
+        before: &Registers,
+        notes: &[String],
+    ) -> Result<(), io::Error> {
+        let line = match self.format {
+            TraceFormat::Notes => notes_line(addr, opcode, notes),
+            TraceFormat::State => state_line(addr, opcode, before),
+        };
+        writeln!(self.writer, "{}", line)
+    }
+
+    pub fn flush(&mut self) -> Result<(), io::Error> {
+        self.writer.flush()
+    }
+}
+
+fn notes_line(addr: u16, opcode: u16, notes: &[String]) -> String {
+    let mut line = format!("{:04X} {:04X}", addr, opcode);
+    for note in notes {
+        line.push(' ');
+        line.push_str(note);
+    }
+    line
+}
+
+fn state_line(addr: u16, opcode: u16, before: &Registers) -> String {
+    let v = before
+        .v
+        .iter()
+        .enumerate()
+        .map(|(reg, val)| format!("V{:X}:{:02X}", reg, val))
+        .collect::<Vec<_>>();
+    format!(
+        "PC:{:04X} OP:{:04X} {} I:{:04X} SP:{:X} DT:{:02X} ST:{:02X}",
+        addr,
+        opcode,
+        v.join(" "),
+        before.i,
+        before.sp,
+        before.delay_timer,
+        before.sound_timer
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_lines() {
+        let mut before = Registers {
+            v: [0; 0x10],
+            i: 0x300,
+            sp: 2,
+            delay_timer: 0x3C,
+            sound_timer: 0,
+        };
+        before.v[0x1] = 0xAB;
+        before.v[0xF] = 0x01;
+
+        assert_eq!(
+            notes_line(0x208, 0xD015, &["erased=3".to_string()]),
+            "0208 D015 erased=3"
+        );
+        assert_eq!(
+            state_line(0x208, 0xD015, &before),
+            "PC:0208 OP:D015 V0:00 V1:AB V2:00 V3:00 V4:00 V5:00 V6:00 V7:00 V8:00 V9:00 VA:00 \
+             VB:00 VC:00 VD:00 VE:00 VF:01 I:0300 SP:2 DT:3C ST:00"
+        );
+    }
+}
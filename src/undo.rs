@@ -0,0 +1,53 @@
+use std::collections::VecDeque;
+
+// Changes made by recent instructions, so the debugger can step back over them one at a time.
+// Registers are few, so they're copied, but of memory, the display and the stack, only what an
+// instruction changed is kept, which is nothing for most instructions.
+
+const MAX_RECORDS: usize = 10_000;
+
+pub struct Undo {
+    pub pc: u16, // of the instruction, as it was before it ran
+    pub i: u16,
+    pub v: [u8; 0x10],
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub stack_len: usize,
+    pub popped: Option<u16>,      // by RET
+    pub memory: Vec<(usize, u8)>, // (address, old value), in the order written
+    pub toggled: Vec<(u16, u16)>, // blocks flipped by DRW
+    pub cleared: Option<Vec<u8>>, // the framebuffer before CLS
+}
+
+pub struct UndoLog {
+    records: VecDeque<Undo>,
+}
+
+impl UndoLog {
+    pub fn new() -> Self {
+        Self {
+            records: VecDeque::new(),
+        }
+    }
+
+    // the oldest record is dropped once the log is full
+    pub fn push(&mut self, undo: Undo) {
+        if self.records.len() == MAX_RECORDS {
+            self.records.pop_front();
+        }
+        self.records.push_back(undo);
+    }
+
+    // the record of the instruction running, for it to add its changes to
+    pub fn current(&mut self) -> Option<&mut Undo> {
+        self.records.back_mut()
+    }
+
+    pub fn pop(&mut self) -> Option<Undo> {
+        self.records.pop_back()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}